@@ -0,0 +1,166 @@
+use async_trait::async_trait;
+use futures::stream::{self, BoxStream, StreamExt, TryStreamExt};
+use std::collections::HashMap;
+
+use crate::{
+    AllComments, Comment, CommentOrder, CommentSet, CommentsTerms, EntityRef, Error,
+    LenientComments, LessWrongApiClient, PagingOptions, Post, PostId, PostTag, PostsQuery,
+};
+
+/// The operations of [`LessWrongApiClient`], so that code depending on it can be tested against
+/// another implementation such as [`crate::FakeLessWrong`].
+#[async_trait]
+pub trait LessWrongApi: Send + Sync {
+    async fn get_post(&self, post_id: &PostId) -> Result<Post, Error>;
+
+    async fn get_post_by_slug(&self, slug: &str) -> Result<Post, Error>;
+
+    /// Implementations without the posts of the old LessWrong return `Error::Unsupported`.
+    async fn get_post_by_legacy_id(&self, legacy_id: u64) -> Result<Post, Error> {
+        let _ = legacy_id;
+        Err(Error::Unsupported("get_post_by_legacy_id"))
+    }
+
+    async fn get_post_by_url(&self, url: &str) -> Result<Post, Error> {
+        match EntityRef::parse(url)? {
+            EntityRef::Post { id, .. } | EntityRef::Comment { post_id: id, .. } => {
+                self.get_post(&id).await
+            }
+            EntityRef::PostSlug { slug } => self.get_post_by_slug(&slug).await,
+            EntityRef::LegacyPost { legacy_id }
+            | EntityRef::LegacyComment {
+                post_legacy_id: legacy_id,
+                ..
+            } => self.get_post_by_legacy_id(legacy_id).await,
+            _ => Err(Error::InvalidUrl(url.to_string())),
+        }
+    }
+
+    async fn get_posts(&self, post_ids: &[PostId]) -> HashMap<PostId, Result<Post, Error>> {
+        let mut posts = HashMap::with_capacity(post_ids.len());
+        for post_id in post_ids {
+            posts.insert(post_id.clone(), self.get_post(post_id).await);
+        }
+        posts
+    }
+
+    async fn get_post_tags(&self, post_id: &PostId) -> Result<Vec<PostTag>, Error> {
+        Ok(self.get_post(post_id).await?.tags)
+    }
+
+    /// Implementations without post listings yield a single `Error::Unsupported`.
+    fn list_posts(&self, query: PostsQuery) -> BoxStream<'static, Result<Post, Error>> {
+        let _ = query;
+        stream::iter([Err(Error::Unsupported("list_posts"))]).boxed()
+    }
+
+    /// Implementations without comment queries return `Error::Unsupported`.
+    async fn query_comments(&self, terms: &CommentsTerms) -> Result<CommentSet, Error> {
+        let _ = terms;
+        Err(Error::Unsupported("query_comments"))
+    }
+
+    /// Defaults to all comments [`Self::get_comments`] returns, without paging.
+    async fn get_all_comments(
+        &self,
+        post_id: &PostId,
+        options: PagingOptions,
+    ) -> Result<AllComments, Error> {
+        let _ = options;
+        let comments = self.get_comments(post_id, i64::MAX).await?;
+        Ok(AllComments {
+            comment_count: comments.len(),
+            received: comments.len(),
+            comments,
+        })
+    }
+
+    /// Defaults to the comments of [`Self::get_all_comments`], oldest first.
+    fn all_comments_stream(
+        &self,
+        post_id: &PostId,
+        options: PagingOptions,
+    ) -> BoxStream<'_, Result<Comment, Error>> {
+        let post_id = post_id.clone();
+        stream::once(async move { self.get_all_comments(&post_id, options).await })
+            .map_ok(|all| {
+                let mut comments = all.comments;
+                comments.sort(CommentOrder::Old);
+                stream::iter(comments.into_iter().map(Ok))
+            })
+            .try_flatten()
+            .boxed()
+    }
+
+    async fn get_comments(&self, post_id: &PostId, limit: i64) -> Result<CommentSet, Error>;
+
+    async fn get_comments_lenient(
+        &self,
+        post_id: &PostId,
+        limit: i64,
+    ) -> Result<LenientComments, Error> {
+        Ok(LenientComments {
+            comments: self.get_comments(post_id, limit).await?,
+            warnings: Vec::new(),
+        })
+    }
+}
+
+#[async_trait]
+impl LessWrongApi for LessWrongApiClient {
+    async fn get_post(&self, post_id: &PostId) -> Result<Post, Error> {
+        LessWrongApiClient::get_post(self, post_id).await
+    }
+
+    async fn get_post_by_slug(&self, slug: &str) -> Result<Post, Error> {
+        LessWrongApiClient::get_post_by_slug(self, slug).await
+    }
+
+    async fn get_post_by_legacy_id(&self, legacy_id: u64) -> Result<Post, Error> {
+        LessWrongApiClient::get_post_by_legacy_id(self, legacy_id).await
+    }
+
+    async fn get_posts(&self, post_ids: &[PostId]) -> HashMap<PostId, Result<Post, Error>> {
+        LessWrongApiClient::get_posts(self, post_ids).await
+    }
+
+    async fn get_post_tags(&self, post_id: &PostId) -> Result<Vec<PostTag>, Error> {
+        LessWrongApiClient::get_post_tags(self, post_id).await
+    }
+
+    fn list_posts(&self, query: PostsQuery) -> BoxStream<'static, Result<Post, Error>> {
+        LessWrongApiClient::list_posts(self, query).boxed()
+    }
+
+    async fn query_comments(&self, terms: &CommentsTerms) -> Result<CommentSet, Error> {
+        LessWrongApiClient::query_comments(self, terms).await
+    }
+
+    async fn get_all_comments(
+        &self,
+        post_id: &PostId,
+        options: PagingOptions,
+    ) -> Result<AllComments, Error> {
+        LessWrongApiClient::get_all_comments(self, post_id, options).await
+    }
+
+    fn all_comments_stream(
+        &self,
+        post_id: &PostId,
+        options: PagingOptions,
+    ) -> BoxStream<'_, Result<Comment, Error>> {
+        LessWrongApiClient::all_comments_stream(self, post_id, options).boxed()
+    }
+
+    async fn get_comments(&self, post_id: &PostId, limit: i64) -> Result<CommentSet, Error> {
+        LessWrongApiClient::get_comments(self, post_id, limit).await
+    }
+
+    async fn get_comments_lenient(
+        &self,
+        post_id: &PostId,
+        limit: i64,
+    ) -> Result<LenientComments, Error> {
+        LessWrongApiClient::get_comments_lenient(self, post_id, limit).await
+    }
+}
@@ -0,0 +1,171 @@
+use futures::{stream, Stream, StreamExt};
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+
+use crate::{CommentSet, Error, LessWrongApiClient, Post, PostId};
+
+/// Progress of a bulk fetch, reported after every finished item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Progress {
+    pub done: usize,
+    pub total: usize,
+    pub failed: usize,
+}
+
+/// Options of [`LessWrongApiClient::fetch_many_posts`] and [`LessWrongApiClient::fetch_many_comments`].
+#[derive(Clone)]
+pub struct BulkOptions {
+    concurrency: usize,
+    on_progress: Option<Arc<dyn Fn(Progress) + Send + Sync>>,
+}
+
+impl Default for BulkOptions {
+    fn default() -> Self {
+        Self {
+            concurrency: 4,
+            on_progress: None,
+        }
+    }
+}
+
+impl fmt::Debug for BulkOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BulkOptions")
+            .field("concurrency", &self.concurrency)
+            .field("on_progress", &self.on_progress.is_some())
+            .finish()
+    }
+}
+
+impl BulkOptions {
+    /// Maximum number of requests in flight. Defaults to 4.
+    /// A `RateLimit` on the client still applies on top of this.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    pub fn on_progress(mut self, on_progress: impl Fn(Progress) + Send + Sync + 'static) -> Self {
+        self.on_progress = Some(Arc::new(on_progress));
+        self
+    }
+}
+
+impl LessWrongApiClient {
+    /// Fetches posts concurrently, yielding each one as soon as it arrives (not in input order).
+    pub fn fetch_many_posts<I>(
+        &self,
+        post_ids: I,
+        options: BulkOptions,
+    ) -> impl Stream<Item = (PostId, Result<Post, Error>)> + Send + 'static
+    where
+        I: IntoIterator<Item = PostId>,
+    {
+        let client = self.clone();
+        fetch_many(post_ids, options, move |post_id| {
+            let client = client.clone();
+            async move { client.get_post(&post_id).await }
+        })
+    }
+
+    /// Fetches the comments of many posts concurrently, yielding them as soon as they arrive.
+    pub fn fetch_many_comments<I>(
+        &self,
+        post_ids: I,
+        limit: i64,
+        options: BulkOptions,
+    ) -> impl Stream<Item = (PostId, Result<CommentSet, Error>)> + Send + 'static
+    where
+        I: IntoIterator<Item = PostId>,
+    {
+        let client = self.clone();
+        fetch_many(post_ids, options, move |post_id| {
+            let client = client.clone();
+            async move { client.get_comments(&post_id, limit).await }
+        })
+    }
+}
+
+fn fetch_many<I, T, F, Fut>(
+    ids: I,
+    options: BulkOptions,
+    fetch: F,
+) -> impl Stream<Item = (PostId, Result<T, Error>)> + Send + 'static
+where
+    I: IntoIterator<Item = PostId>,
+    F: Fn(PostId) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<T, Error>> + Send + 'static,
+    T: Send + 'static,
+{
+    let ids: Vec<PostId> = ids.into_iter().collect();
+    let mut progress = Progress {
+        total: ids.len(),
+        ..Default::default()
+    };
+    let on_progress = options.on_progress;
+
+    stream::iter(ids)
+        .map(move |id| {
+            let result = fetch(id.clone());
+            async move { (id, result.await) }
+        })
+        .buffer_unordered(options.concurrency)
+        .inspect(move |(_, result)| {
+            progress.done += 1;
+            if result.is_err() {
+                progress.failed += 1;
+            }
+            if let Some(on_progress) = &on_progress {
+                on_progress(progress);
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{FakeResponse, FakeServer};
+    use std::sync::Mutex;
+
+    #[tokio::test]
+    async fn test_fetch_many_posts() {
+        let server = FakeServer::start().await;
+        server.respond("PostQuery", FakeResponse::fixture("post_query.json"));
+        server.respond_once(
+            "PostQuery",
+            FakeResponse::graphql_error("app.document_not_found"),
+        );
+
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let options = BulkOptions::default().concurrency(1).on_progress({
+            let reports = reports.clone();
+            move |progress| reports.lock().unwrap().push(progress)
+        });
+        let results: Vec<_> = server
+            .client()
+            .fetch_many_posts(
+                [
+                    "MissingPst2345678",
+                    "7ZqGiPHTpiDMwqMN2",
+                    "7ZqGiPHTpiDMwqMN2",
+                ]
+                .map(|id| id.parse().unwrap()),
+                options,
+            )
+            .collect()
+            .await;
+
+        assert_eq!(results.len(), 3);
+        assert!(matches!(results[0], (ref id, Err(Error::NotFound)) if id == "MissingPst2345678"));
+        assert!(results[1].1.is_ok() && results[2].1.is_ok());
+        assert_eq!(
+            reports.lock().unwrap().last(),
+            Some(&Progress {
+                done: 3,
+                total: 3,
+                failed: 1
+            })
+        );
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::JSON;
+
+/// Configuration of the in-memory response cache. Responses are cached per operation and
+/// variables, so every query of the client goes through it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CacheConfig {
+    /// How long a single post stays cached.
+    pub post_ttl: Duration,
+    /// How long the comments of a post stay cached. Comments change more often than posts.
+    pub comments_ttl: Duration,
+    /// TTL for all other queries.
+    pub default_ttl: Duration,
+    /// Maximum number of cached responses. The least recently used one is evicted when full.
+    pub max_entries: usize,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            post_ttl: Duration::from_secs(10 * 60),
+            comments_ttl: Duration::from_secs(60),
+            default_ttl: Duration::from_secs(60),
+            max_entries: 1000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+}
+
+#[derive(Debug)]
+struct Entry {
+    variables: JSON,
+    response: JSON,
+    expires_at: Instant,
+    last_used: u64,
+}
+
+#[derive(Debug)]
+pub(crate) struct ResponseCache {
+    config: CacheConfig,
+    entries: Mutex<HashMap<String, Entry>>,
+    clock: AtomicU64,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl ResponseCache {
+    pub(crate) fn new(config: CacheConfig) -> Self {
+        Self {
+            config,
+            entries: Mutex::new(HashMap::new()),
+            clock: AtomicU64::new(0),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn get(&self, body: &JSON) -> Option<JSON> {
+        let key = key(body);
+        let mut entries = self.entries.lock().unwrap();
+        let response = match entries.get_mut(&key) {
+            Some(entry) if entry.expires_at > Instant::now() => {
+                entry.last_used = self.clock.fetch_add(1, Ordering::Relaxed);
+                Some(entry.response.clone())
+            }
+            Some(_) => {
+                entries.remove(&key);
+                None
+            }
+            None => None,
+        };
+
+        match response {
+            Some(_) => self.hits.fetch_add(1, Ordering::Relaxed),
+            None => self.misses.fetch_add(1, Ordering::Relaxed),
+        };
+        response
+    }
+
+    /// Caches a response, unless it contains GraphQL errors.
+    pub(crate) fn insert(&self, body: &JSON, response: &JSON) {
+        if response
+            .get("errors")
+            .is_some_and(|errors| !errors.is_null())
+            || self.config.max_entries == 0
+        {
+            return;
+        }
+
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.config.max_entries {
+            entries.retain(|_, entry| entry.expires_at > now);
+        }
+        if entries.len() >= self.config.max_entries {
+            let lru = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone());
+            if let Some(lru) = lru {
+                entries.remove(&lru);
+            }
+        }
+
+        entries.insert(
+            key(body),
+            Entry {
+                variables: body["variables"].clone(),
+                response: response.clone(),
+                expires_at: now + self.ttl(body["operationName"].as_str().unwrap_or_default()),
+                last_used: self.clock.fetch_add(1, Ordering::Relaxed),
+            },
+        );
+    }
+
+    /// Removes all cached responses of queries that reference the given ID in their variables.
+    pub(crate) fn invalidate(&self, id: &str) {
+        self.entries
+            .lock()
+            .unwrap()
+            .retain(|_, entry| !references(&entry.variables, id));
+    }
+
+    pub(crate) fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    pub(crate) fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: self.entries.lock().unwrap().len(),
+        }
+    }
+
+    fn ttl(&self, operation_name: &str) -> Duration {
+        match operation_name {
+            "PostQuery"
+            | "PostBySlugQuery"
+            | "PostByLegacyIdQuery"
+            | "PostsBatchQuery"
+            | "PostTagsQuery" => self.config.post_ttl,
+            "CommentsQuery" | "PostCommentCountQuery" => self.config.comments_ttl,
+            _ => self.config.default_ttl,
+        }
+    }
+}
+
+fn key(body: &JSON) -> String {
+    // serde_json sorts object keys, so equal variables give equal keys
+    format!("{}:{}", body["operationName"], body["variables"])
+}
+
+fn references(value: &JSON, id: &str) -> bool {
+    match value {
+        JSON::String(s) => s == id,
+        JSON::Array(values) => values.iter().any(|v| references(v, id)),
+        JSON::Object(map) => map.values().any(|v| references(v, id)),
+        _ => false,
+    }
+}
@@ -0,0 +1,202 @@
+use indexmap::IndexMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp::Ordering;
+use std::ops::Index;
+
+use crate::{Comment, CommentId};
+
+/// How [`CommentSet::sort`] orders comments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommentOrder {
+    /// Highest `base_score` first, like the `postCommentsTop` view.
+    Top,
+    /// Newest first.
+    New,
+    /// Oldest first.
+    Old,
+    /// Most votes first.
+    MostVotes,
+}
+
+impl CommentOrder {
+    pub(crate) fn compare(self, a: &Comment, b: &Comment) -> Ordering {
+        match self {
+            CommentOrder::Top => b.base_score.total_cmp(&a.base_score),
+            CommentOrder::New => b.posted_at.cmp(&a.posted_at),
+            CommentOrder::Old => a.posted_at.cmp(&b.posted_at),
+            CommentOrder::MostVotes => b.vote_count.total_cmp(&a.vote_count),
+        }
+    }
+}
+
+/// Comments in the order the server returned them, which can also be looked up by ID.
+///
+/// Serializes as a list of comments.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CommentSet {
+    comments: IndexMap<CommentId, Comment>,
+}
+
+impl CommentSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.comments.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.comments.is_empty()
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Comment> {
+        self.comments.get(id)
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.comments.contains_key(id)
+    }
+
+    /// The position of the comment in the current order.
+    pub fn position(&self, id: &str) -> Option<usize> {
+        self.comments.get_index_of(id)
+    }
+
+    /// Appends the comment, or replaces the comment with the same ID in place.
+    pub fn insert(&mut self, comment: Comment) -> Option<Comment> {
+        self.comments.insert(comment.id.clone(), comment)
+    }
+
+    /// Removes the comment, keeping the order of the others.
+    pub fn remove(&mut self, id: &str) -> Option<Comment> {
+        self.comments.shift_remove(id)
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &Comment> + ExactSizeIterator {
+        self.comments.values()
+    }
+
+    pub fn ids(&self) -> impl DoubleEndedIterator<Item = &CommentId> + ExactSizeIterator {
+        self.comments.keys()
+    }
+
+    /// Re-sorts the comments. Ties keep their current order.
+    pub fn sort(&mut self, order: CommentOrder) {
+        self.sort_by(|a, b| order.compare(a, b));
+    }
+
+    pub fn sort_by(&mut self, mut compare: impl FnMut(&Comment, &Comment) -> Ordering) {
+        self.comments.sort_by(|_, a, _, b| compare(a, b));
+    }
+}
+
+impl Index<&str> for CommentSet {
+    type Output = Comment;
+
+    fn index(&self, id: &str) -> &Comment {
+        self.get(id)
+            .unwrap_or_else(|| panic!("no comment with ID '{}'", id))
+    }
+}
+
+impl FromIterator<Comment> for CommentSet {
+    fn from_iter<I: IntoIterator<Item = Comment>>(comments: I) -> Self {
+        let mut set = Self::new();
+        set.extend(comments);
+        set
+    }
+}
+
+impl Extend<Comment> for CommentSet {
+    fn extend<I: IntoIterator<Item = Comment>>(&mut self, comments: I) {
+        for comment in comments {
+            self.insert(comment);
+        }
+    }
+}
+
+impl IntoIterator for CommentSet {
+    type Item = Comment;
+    type IntoIter = indexmap::map::IntoValues<CommentId, Comment>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.comments.into_values()
+    }
+}
+
+impl<'a> IntoIterator for &'a CommentSet {
+    type Item = &'a Comment;
+    type IntoIter = indexmap::map::Values<'a, CommentId, Comment>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.comments.values()
+    }
+}
+
+impl Serialize for CommentSet {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+impl<'de> Deserialize<'de> for CommentSet {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Vec::<Comment>::deserialize(deserializer)?
+            .into_iter()
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_comment_set_order() {
+        let comment = |id: &str, base_score, vote_count, posted_at: &str| Comment {
+            id: id.parse().unwrap(),
+            base_score,
+            vote_count,
+            posted_at: posted_at.parse().unwrap(),
+            ..Default::default()
+        };
+        let mut comments: CommentSet = [
+            comment("aaaaaaaaaaaaaaaaa", 5.0, 2.0, "2020-01-02T00:00:00Z"),
+            comment("bbbbbbbbbbbbbbbbb", 9.0, 1.0, "2020-01-01T00:00:00Z"),
+            comment("ccccccccccccccccc", 5.0, 3.0, "2020-01-03T00:00:00Z"),
+        ]
+        .into_iter()
+        .collect();
+        let ids = |comments: &CommentSet| -> Vec<String> {
+            comments.ids().map(|id| id.to_string()).collect()
+        };
+
+        assert_eq!(ids(&comments)[0], "aaaaaaaaaaaaaaaaa");
+        assert_eq!(comments["bbbbbbbbbbbbbbbbb"].base_score, 9.0);
+
+        comments.sort(CommentOrder::Top);
+        assert_eq!(
+            ids(&comments),
+            [
+                "bbbbbbbbbbbbbbbbb",
+                "aaaaaaaaaaaaaaaaa",
+                "ccccccccccccccccc"
+            ]
+        );
+        comments.sort(CommentOrder::New);
+        assert_eq!(comments.position("ccccccccccccccccc"), Some(0));
+        comments.sort(CommentOrder::MostVotes);
+        assert_eq!(
+            ids(&comments),
+            [
+                "ccccccccccccccccc",
+                "aaaaaaaaaaaaaaaaa",
+                "bbbbbbbbbbbbbbbbb"
+            ]
+        );
+
+        let json = serde_json::to_string(&comments).unwrap();
+        assert_eq!(serde_json::from_str::<CommentSet>(&json).unwrap(), comments);
+    }
+}
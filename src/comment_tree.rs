@@ -0,0 +1,389 @@
+use std::cmp::Ordering;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+use crate::{Comment, CommentId, CommentOrder, CommentSet};
+
+/// What [`CommentTree`] does with replies whose parent is not part of the comments,
+/// e.g. because it was deleted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OrphanPolicy {
+    /// Groups the replies under a placeholder node for the missing parent, which is a root.
+    #[default]
+    Placeholder,
+    /// Makes the replies roots themselves.
+    Promote,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Node {
+    id: CommentId,
+    /// `None` for placeholders.
+    comment: Option<Comment>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    depth: usize,
+}
+
+/// The reply structure of a set of comments.
+///
+/// Roots and the children of every comment keep the order of the [`CommentSet`] the tree was
+/// built from until they are sorted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommentTree {
+    nodes: Vec<Node>,
+    roots: Vec<usize>,
+    index: HashMap<CommentId, usize>,
+}
+
+impl From<CommentSet> for CommentTree {
+    fn from(comments: CommentSet) -> Self {
+        Self::new(comments, OrphanPolicy::default())
+    }
+}
+
+impl CommentTree {
+    pub fn new(comments: CommentSet, orphans: OrphanPolicy) -> Self {
+        let mut tree = CommentTree {
+            nodes: Vec::with_capacity(comments.len()),
+            roots: Vec::new(),
+            index: HashMap::with_capacity(comments.len()),
+        };
+        for comment in comments {
+            tree.index.insert(comment.id.clone(), tree.nodes.len());
+            tree.nodes.push(Node {
+                id: comment.id.clone(),
+                comment: Some(comment),
+                parent: None,
+                children: Vec::new(),
+                depth: 0,
+            });
+        }
+
+        for i in 0..tree.nodes.len() {
+            let parent_id = tree.nodes[i]
+                .comment
+                .as_ref()
+                .and_then(|c| c.parent_comment_id.clone());
+            let parent = match parent_id {
+                None => None,
+                Some(parent_id) => match tree.index.get(&parent_id) {
+                    Some(&parent) => Some(parent),
+                    None if orphans == OrphanPolicy::Placeholder => {
+                        Some(tree.placeholder(parent_id))
+                    }
+                    None => None,
+                },
+            };
+            match parent {
+                Some(parent) => tree.attach(i, parent),
+                None => tree.roots.push(i),
+            }
+        }
+
+        tree.break_cycles();
+        tree.update_depths();
+        tree
+    }
+
+    /// The node of a missing parent, created as a root the first time it is needed.
+    fn placeholder(&mut self, id: CommentId) -> usize {
+        if let Some(&i) = self.index.get(&id) {
+            return i;
+        }
+        let i = self.nodes.len();
+        self.index.insert(id.clone(), i);
+        self.nodes.push(Node {
+            id,
+            comment: None,
+            parent: None,
+            children: Vec::new(),
+            depth: 0,
+        });
+        self.roots.push(i);
+        i
+    }
+
+    fn attach(&mut self, child: usize, parent: usize) {
+        self.nodes[child].parent = Some(parent);
+        self.nodes[parent].children.push(child);
+    }
+
+    /// Comments replying to each other in a cycle are not reachable from any root,
+    /// the first comment of each cycle becomes a root.
+    fn break_cycles(&mut self) {
+        let mut reachable = vec![false; self.nodes.len()];
+        let mut stack = self.roots.clone();
+        loop {
+            while let Some(i) = stack.pop() {
+                reachable[i] = true;
+                stack.extend(&self.nodes[i].children);
+            }
+            let Some(i) = reachable.iter().position(|reachable| !reachable) else {
+                return;
+            };
+            if let Some(parent) = self.nodes[i].parent.take() {
+                self.nodes[parent].children.retain(|&child| child != i);
+            }
+            self.roots.push(i);
+            stack.push(i);
+        }
+    }
+
+    fn update_depths(&mut self) {
+        let mut queue: VecDeque<(usize, usize)> = self.roots.iter().map(|&i| (i, 0)).collect();
+        while let Some((i, depth)) = queue.pop_front() {
+            self.nodes[i].depth = depth;
+            queue.extend(
+                self.nodes[i]
+                    .children
+                    .iter()
+                    .map(|&child| (child, depth + 1)),
+            );
+        }
+    }
+
+    /// Number of nodes, including placeholders.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn get(&self, id: &str) -> Option<CommentNode<'_>> {
+        self.index.get(id).map(|&i| self.node(i))
+    }
+
+    pub fn roots(&self) -> impl ExactSizeIterator<Item = CommentNode<'_>> {
+        self.roots.iter().map(|&i| self.node(i))
+    }
+
+    /// All nodes, each followed by its replies.
+    pub fn pre_order(&self) -> PreOrder<'_> {
+        PreOrder {
+            tree: self,
+            stack: self.roots.iter().rev().copied().collect(),
+        }
+    }
+
+    /// All nodes, level by level.
+    pub fn breadth_first(&self) -> BreadthFirst<'_> {
+        BreadthFirst {
+            tree: self,
+            queue: self.roots.iter().copied().collect(),
+        }
+    }
+
+    /// Sorts the roots and the replies of every comment.
+    pub fn sort(&mut self, order: CommentOrder) {
+        self.sort_by_level(|_| order);
+    }
+
+    /// Sorts every level with its own order, e.g. the roots by score and replies by date.
+    /// The roots are level 0. Placeholders go after the comments of their level.
+    pub fn sort_by_level(&mut self, order: impl Fn(usize) -> CommentOrder) {
+        let nodes = &self.nodes;
+        let compare =
+            |depth: usize, a: &usize, b: &usize| match (&nodes[*a].comment, &nodes[*b].comment) {
+                (Some(a), Some(b)) => order(depth).compare(a, b),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            };
+
+        let mut roots = self.roots.clone();
+        roots.sort_by(|a, b| compare(0, a, b));
+        let children: Vec<Vec<usize>> = nodes
+            .iter()
+            .map(|node| {
+                let mut children = node.children.clone();
+                children.sort_by(|a, b| compare(node.depth + 1, a, b));
+                children
+            })
+            .collect();
+
+        self.roots = roots;
+        for (node, children) in self.nodes.iter_mut().zip(children) {
+            node.children = children;
+        }
+    }
+
+    fn node(&self, i: usize) -> CommentNode<'_> {
+        CommentNode { tree: self, i }
+    }
+}
+
+/// A comment, or a placeholder for a missing parent, in a [`CommentTree`].
+#[derive(Clone, Copy)]
+pub struct CommentNode<'a> {
+    tree: &'a CommentTree,
+    i: usize,
+}
+
+impl fmt::Debug for CommentNode<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CommentNode")
+            .field("id", self.id())
+            .field("depth", &self.depth())
+            .field("is_placeholder", &self.is_placeholder())
+            .finish()
+    }
+}
+
+impl<'a> CommentNode<'a> {
+    pub fn id(&self) -> &'a CommentId {
+        &self.tree.nodes[self.i].id
+    }
+
+    /// `None` for placeholders.
+    pub fn comment(&self) -> Option<&'a Comment> {
+        self.tree.nodes[self.i].comment.as_ref()
+    }
+
+    pub fn is_placeholder(&self) -> bool {
+        self.comment().is_none()
+    }
+
+    pub fn parent(&self) -> Option<CommentNode<'a>> {
+        self.tree.nodes[self.i].parent.map(|i| self.tree.node(i))
+    }
+
+    pub fn children(&self) -> impl ExactSizeIterator<Item = CommentNode<'a>> + 'a {
+        let tree = self.tree;
+        tree.nodes[self.i].children.iter().map(|&i| tree.node(i))
+    }
+
+    /// 0 for roots.
+    pub fn depth(&self) -> usize {
+        self.tree.nodes[self.i].depth
+    }
+
+    /// Number of nodes in the subtree, including this one.
+    pub fn subtree_size(&self) -> usize {
+        self.pre_order().count()
+    }
+
+    /// This node followed by all its replies, each followed by its own replies.
+    pub fn pre_order(&self) -> PreOrder<'a> {
+        PreOrder {
+            tree: self.tree,
+            stack: vec![self.i],
+        }
+    }
+
+    /// This node followed by all its replies, level by level.
+    pub fn breadth_first(&self) -> BreadthFirst<'a> {
+        BreadthFirst {
+            tree: self.tree,
+            queue: VecDeque::from([self.i]),
+        }
+    }
+}
+
+impl PartialEq for CommentNode<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self.tree, other.tree) && self.i == other.i
+    }
+}
+
+/// See [`CommentTree::pre_order`].
+#[derive(Debug, Clone)]
+pub struct PreOrder<'a> {
+    tree: &'a CommentTree,
+    stack: Vec<usize>,
+}
+
+impl<'a> Iterator for PreOrder<'a> {
+    type Item = CommentNode<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let i = self.stack.pop()?;
+        self.stack
+            .extend(self.tree.nodes[i].children.iter().rev().copied());
+        Some(self.tree.node(i))
+    }
+}
+
+/// See [`CommentTree::breadth_first`].
+#[derive(Debug, Clone)]
+pub struct BreadthFirst<'a> {
+    tree: &'a CommentTree,
+    queue: VecDeque<usize>,
+}
+
+impl<'a> Iterator for BreadthFirst<'a> {
+    type Item = CommentNode<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let i = self.queue.pop_front()?;
+        self.queue.extend(&self.tree.nodes[i].children);
+        Some(self.tree.node(i))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn comment(id: &str, parent: Option<&str>, base_score: f64) -> Comment {
+        Comment {
+            id: id.repeat(17).parse().unwrap(),
+            parent_comment_id: parent.map(|parent| parent.repeat(17).parse().unwrap()),
+            base_score,
+            ..Default::default()
+        }
+    }
+
+    fn ids<'a>(nodes: impl Iterator<Item = CommentNode<'a>>) -> String {
+        nodes.map(|node| &node.id().as_str()[..1]).collect()
+    }
+
+    #[test]
+    fn test_comment_tree() {
+        // b replies to a, c to b, d to a; f replies to the missing e
+        let comments: CommentSet = [
+            comment("a", None, 1.0),
+            comment("b", Some("a"), 1.0),
+            comment("c", Some("b"), 1.0),
+            comment("d", Some("a"), 5.0),
+            comment("f", Some("e"), 1.0),
+            comment("g", None, 3.0),
+        ]
+        .into_iter()
+        .collect();
+
+        let mut tree = CommentTree::from(comments.clone());
+        assert_eq!(tree.len(), 7);
+        assert_eq!(ids(tree.roots()), "aeg");
+        assert_eq!(ids(tree.pre_order()), "abcdefg");
+        assert_eq!(ids(tree.breadth_first()), "aegbdfc");
+
+        let c = tree.get("ccccccccccccccccc").unwrap();
+        assert_eq!(c.depth(), 2);
+        assert_eq!(ids(c.parent().unwrap().parent().into_iter()), "a");
+        let e = tree.get("eeeeeeeeeeeeeeeee").unwrap();
+        assert!(e.is_placeholder());
+        assert_eq!(ids(e.children()), "f");
+        assert_eq!(tree.get("aaaaaaaaaaaaaaaaa").unwrap().subtree_size(), 4);
+
+        tree.sort(CommentOrder::Top);
+        assert_eq!(ids(tree.roots()), "gae");
+        assert_eq!(ids(tree.pre_order()), "gadbcef");
+
+        let promoted = CommentTree::new(comments, OrphanPolicy::Promote);
+        assert_eq!(promoted.len(), 6);
+        assert_eq!(ids(promoted.roots()), "afg");
+    }
+
+    #[test]
+    fn test_comment_tree_cycle() {
+        let comments: CommentSet = [comment("a", Some("b"), 1.0), comment("b", Some("a"), 1.0)]
+            .into_iter()
+            .collect();
+        let tree = CommentTree::from(comments);
+        assert_eq!(ids(tree.roots()), "a");
+        assert_eq!(ids(tree.pre_order()), "ab");
+    }
+}
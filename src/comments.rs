@@ -0,0 +1,535 @@
+use chrono::{DateTime, Utc};
+use futures::{future, stream, Stream, TryStreamExt};
+use std::collections::HashSet;
+
+use crate::{
+    comments_query, post_comment_count_query, Comment, CommentId, CommentOrder, CommentSet, Error,
+    LessWrongApiClient, PostCommentCountQuery, PostId, UserId, JSON,
+};
+
+/// The views of the `comments` resolver, which decide the order and the base filter of the results.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum CommentsView {
+    /// Comments of a post, highest-scored first.
+    #[default]
+    PostCommentsTop,
+    PostCommentsNew,
+    PostCommentsOld,
+    PostCommentsMagic,
+    /// Comments of all posts, newest first.
+    RecentComments,
+    /// Comments of the user set with [`CommentsTerms::user_id`], newest first.
+    ProfileComments,
+    /// Shortform posts, optionally of a single user.
+    Shortform,
+    /// The answers to a question post.
+    QuestionAnswers,
+    /// Direct replies to the comment set with [`CommentsTerms::parent_comment_id`].
+    CommentReplies,
+    /// The whole thread below the comment set with [`CommentsTerms::top_level_comment_id`].
+    RepliesToCommentThread,
+    /// Any other view the server knows.
+    Custom(String),
+}
+
+impl CommentsView {
+    pub fn as_str(&self) -> &str {
+        match self {
+            CommentsView::PostCommentsTop => "postCommentsTop",
+            CommentsView::PostCommentsNew => "postCommentsNew",
+            CommentsView::PostCommentsOld => "postCommentsOld",
+            CommentsView::PostCommentsMagic => "postCommentsMagic",
+            CommentsView::RecentComments => "recentComments",
+            CommentsView::ProfileComments => "profileComments",
+            CommentsView::Shortform => "shortform",
+            CommentsView::QuestionAnswers => "questionAnswers",
+            CommentsView::CommentReplies => "commentReplies",
+            CommentsView::RepliesToCommentThread => "repliesToCommentThread",
+            CommentsView::Custom(view) => view,
+        }
+    }
+}
+
+/// The `terms` of a comments query.
+///
+/// `terms` is a `JSON` scalar in the schema, so its fields cannot be GraphQL variables of their
+/// own. This builds the whole object instead.
+///
+/// ```
+/// # use lesswrong_api::{CommentsTerms, CommentsView};
+/// let terms = CommentsTerms::for_post("7ZqGiPHTpiDMwqMN2".parse().unwrap())
+///     .view(CommentsView::PostCommentsNew)
+///     .limit(100);
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CommentsTerms {
+    view: CommentsView,
+    post_id: Option<PostId>,
+    user_id: Option<UserId>,
+    parent_comment_id: Option<CommentId>,
+    top_level_comment_id: Option<CommentId>,
+    after: Option<DateTime<Utc>>,
+    before: Option<DateTime<Utc>>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+}
+
+impl CommentsTerms {
+    pub fn new(view: CommentsView) -> Self {
+        Self {
+            view,
+            ..Default::default()
+        }
+    }
+
+    /// The comments of a post with the `postCommentsTop` view.
+    pub fn for_post(post_id: PostId) -> Self {
+        Self::new(CommentsView::PostCommentsTop).post_id(post_id)
+    }
+
+    pub fn view(mut self, view: CommentsView) -> Self {
+        self.view = view;
+        self
+    }
+
+    pub fn post_id(mut self, post_id: PostId) -> Self {
+        self.post_id = Some(post_id);
+        self
+    }
+
+    /// Only comments of this user.
+    pub fn user_id(mut self, user_id: UserId) -> Self {
+        self.user_id = Some(user_id);
+        self
+    }
+
+    pub fn parent_comment_id(mut self, parent_comment_id: CommentId) -> Self {
+        self.parent_comment_id = Some(parent_comment_id);
+        self
+    }
+
+    pub fn top_level_comment_id(mut self, top_level_comment_id: CommentId) -> Self {
+        self.top_level_comment_id = Some(top_level_comment_id);
+        self
+    }
+
+    /// Only comments posted at or after `after`.
+    pub fn after(mut self, after: DateTime<Utc>) -> Self {
+        self.after = Some(after);
+        self
+    }
+
+    /// Only comments posted before `before`.
+    pub fn before(mut self, before: DateTime<Utc>) -> Self {
+        self.before = Some(before);
+        self
+    }
+
+    pub fn offset(mut self, offset: usize) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+
+    /// The server's default applies if no limit is set.
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Filters, orders and pages the comments of posts like the server would, for
+    /// [`crate::FakeLessWrong`]. Views without an obvious order list the highest-scored first.
+    pub(crate) fn apply<'a>(
+        &self,
+        comments: impl IntoIterator<Item = (&'a PostId, &'a Comment)>,
+    ) -> CommentSet {
+        let mut comments: CommentSet = comments
+            .into_iter()
+            .filter(|(post_id, comment)| {
+                self.post_id.as_ref().is_none_or(|id| id == *post_id)
+                    && self
+                        .user_id
+                        .as_ref()
+                        .is_none_or(|id| comment.user_id.as_ref() == Some(id))
+                    && self
+                        .parent_comment_id
+                        .as_ref()
+                        .is_none_or(|id| comment.parent_comment_id.as_ref() == Some(id))
+                    && self
+                        .top_level_comment_id
+                        .as_ref()
+                        .is_none_or(|id| comment.top_level_comment_id.as_ref() == Some(id))
+                    && self.after.is_none_or(|after| comment.posted_at >= after)
+                    && self.before.is_none_or(|before| comment.posted_at < before)
+            })
+            .map(|(_, comment)| comment.clone())
+            .collect();
+        comments.sort(match self.view {
+            CommentsView::PostCommentsOld => CommentOrder::Old,
+            CommentsView::PostCommentsNew
+            | CommentsView::RecentComments
+            | CommentsView::ProfileComments
+            | CommentsView::Shortform => CommentOrder::New,
+            _ => CommentOrder::Top,
+        });
+        comments
+            .into_iter()
+            .skip(self.offset.unwrap_or(0))
+            .take(self.limit.unwrap_or(usize::MAX))
+            .collect()
+    }
+
+    pub(crate) fn to_json(&self) -> JSON {
+        let mut terms = serde_json::Map::new();
+        terms.insert("view".to_string(), self.view.as_str().into());
+        let ids = [
+            ("postId", self.post_id.as_ref().map(PostId::as_str)),
+            ("userId", self.user_id.as_ref().map(UserId::as_str)),
+            (
+                "parentCommentId",
+                self.parent_comment_id.as_ref().map(CommentId::as_str),
+            ),
+            (
+                "topLevelCommentId",
+                self.top_level_comment_id.as_ref().map(CommentId::as_str),
+            ),
+        ];
+        for (key, id) in ids {
+            if let Some(id) = id {
+                terms.insert(key.to_string(), id.into());
+            }
+        }
+        if let Some(after) = self.after {
+            terms.insert("after".to_string(), after.to_rfc3339().into());
+        }
+        if let Some(before) = self.before {
+            terms.insert("before".to_string(), before.to_rfc3339().into());
+        }
+        if let Some(offset) = self.offset {
+            terms.insert("offset".to_string(), offset.into());
+        }
+        if let Some(limit) = self.limit {
+            terms.insert("limit".to_string(), limit.into());
+        }
+        JSON::Object(terms)
+    }
+}
+
+/// Options of [`LessWrongApiClient::get_all_comments`] and
+/// [`LessWrongApiClient::all_comments_stream`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PagingOptions {
+    page_size: usize,
+    concurrency: usize,
+}
+
+impl Default for PagingOptions {
+    fn default() -> Self {
+        Self {
+            page_size: 100,
+            concurrency: 1,
+        }
+    }
+}
+
+impl PagingOptions {
+    /// Number of comments requested at once. Defaults to 100.
+    pub fn page_size(mut self, page_size: usize) -> Self {
+        self.page_size = page_size.max(1);
+        self
+    }
+
+    /// Maximum number of pages requested at once. Defaults to 1.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+}
+
+/// All comments of a post, see [`LessWrongApiClient::get_all_comments`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct AllComments {
+    pub comments: CommentSet,
+    /// The post's `commentCount` before the first page was requested.
+    pub comment_count: usize,
+    /// Distinct comments received, including the ones skipped by the `CommentFilter`.
+    pub received: usize,
+}
+
+impl AllComments {
+    /// Whether as many comments were received as the post counts. Comments can be missed if
+    /// earlier ones are deleted while paging, which moves them to an already requested page.
+    pub fn is_complete(&self) -> bool {
+        self.received >= self.comment_count
+    }
+}
+
+type RawComment = comments_query::CommentsQueryCommentsResults;
+
+/// The state of [`LessWrongApiClient::raw_comment_pages`].
+struct Pager {
+    client: LessWrongApiClient,
+    post_id: PostId,
+    options: PagingOptions,
+    comment_count: Option<usize>,
+    next_offset: usize,
+    seen: HashSet<String>,
+}
+
+impl LessWrongApiClient {
+    /// Fetches all comments of a post in pages of `page_size`, however many there are.
+    /// The comments are sorted with `CommentOrder::Top`, like the ones of [`Self::get_comments`].
+    pub async fn get_all_comments(
+        &self,
+        post_id: &PostId,
+        options: PagingOptions,
+    ) -> Result<AllComments, Error> {
+        let pages = self.raw_comment_pages(post_id, options);
+        futures::pin_mut!(pages);
+
+        let mut all = AllComments {
+            comments: CommentSet::new(),
+            comment_count: 0,
+            received: 0,
+        };
+        while let Some((comment_count, page)) = pages.try_next().await? {
+            all.comment_count = comment_count;
+            all.received += page.len();
+            for c in page.into_iter().filter(|c| self.keeps_comment(c)) {
+                let comment = self.decode_comment(c)?;
+                all.comments.insert(comment);
+            }
+        }
+        all.comments.sort(CommentOrder::Top);
+        Ok(all)
+    }
+
+    /// Like [`Self::get_all_comments`] but yields the comments page by page, oldest first.
+    pub fn all_comments_stream(
+        &self,
+        post_id: &PostId,
+        options: PagingOptions,
+    ) -> impl Stream<Item = Result<Comment, Error>> + Send + 'static {
+        let client = self.clone();
+        self.raw_comment_pages(post_id, options)
+            .map_ok(move |(_, page)| {
+                let comments: Vec<_> = page
+                    .into_iter()
+                    .filter(|c| client.keeps_comment(c))
+                    .map(|c| client.decode_comment(c).map_err(Error::from))
+                    .collect();
+                stream::iter(comments)
+            })
+            .try_flatten()
+    }
+
+    /// Yields the post's `commentCount` with every page of comments not seen on an earlier page.
+    ///
+    /// Pages up to the `commentCount` are requested `concurrency` at a time. Paging continues one
+    /// page at a time past it until a page is not full, in case comments were added meanwhile.
+    /// The pages are of the `postCommentsOld` view, whose order does not change with votes.
+    fn raw_comment_pages(
+        &self,
+        post_id: &PostId,
+        options: PagingOptions,
+    ) -> impl Stream<Item = Result<(usize, Vec<RawComment>), Error>> + Send + 'static {
+        let pager = Pager {
+            client: self.clone(),
+            post_id: post_id.clone(),
+            options,
+            comment_count: None,
+            next_offset: 0,
+            seen: HashSet::new(),
+        };
+        stream::try_unfold(Some(pager), |pager| async move {
+            let Some(mut pager) = pager else {
+                return Ok::<_, Error>(None);
+            };
+            let comment_count = match pager.comment_count {
+                Some(comment_count) => comment_count,
+                None => pager.client.get_comment_count(&pager.post_id).await?,
+            };
+            pager.comment_count = Some(comment_count);
+
+            let page_size = pager.options.page_size;
+            let remaining_pages = comment_count
+                .saturating_sub(pager.next_offset)
+                .div_ceil(page_size);
+            let offsets: Vec<usize> = (0..remaining_pages.clamp(1, pager.options.concurrency))
+                .map(|i| pager.next_offset + i * page_size)
+                .collect();
+            let pages = future::try_join_all(offsets.iter().map(|offset| {
+                let terms = CommentsTerms::new(CommentsView::PostCommentsOld)
+                    .post_id(pager.post_id.clone())
+                    .offset(*offset)
+                    .limit(page_size);
+                let client = &pager.client;
+                async move { client.fetch_raw_comment_results(&terms).await }
+            }))
+            .await?;
+
+            let is_last = pages.iter().any(|page| page.len() < page_size);
+            pager.next_offset += offsets.len() * page_size;
+            let new: Vec<_> = pages
+                .into_iter()
+                .flatten()
+                .filter(|c| match &c.id {
+                    Some(id) => pager.seen.insert(id.clone()),
+                    None => true,
+                })
+                .collect();
+            Ok(Some(((comment_count, new), (!is_last).then_some(pager))))
+        })
+    }
+
+    async fn get_comment_count(&self, post_id: &PostId) -> Result<usize, Error> {
+        let variables = post_comment_count_query::Variables {
+            id: post_id.to_string(),
+        };
+        let comment_count = self
+            .post_graphql::<PostCommentCountQuery>(variables)
+            .await?
+            .post
+            .ok_or(Error::NotFound)?
+            .result
+            .ok_or(Error::NotFound)?
+            .comment_count
+            .ok_or(Error::malformatted("post.comment_count"))?;
+        Ok(comment_count.max(0.0) as usize)
+    }
+
+    /// Fetches the comments of any view, see [`Self::get_comments`] for the comments of a post.
+    pub async fn query_comments(&self, terms: &CommentsTerms) -> Result<CommentSet, Error> {
+        self.fetch_comment_results(terms)
+            .await?
+            .into_iter()
+            .map(|c| Ok(self.decode_comment(c)?))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{FakeResponse, FakeServer};
+    use futures::StreamExt;
+
+    #[test]
+    fn test_comments_terms() {
+        let terms = CommentsTerms::new(CommentsView::ProfileComments)
+            .user_id("nmk3nLpQE89dMRzzN".parse().unwrap())
+            .after("2020-01-01T00:00:00Z".parse().unwrap())
+            .limit(10);
+        assert_eq!(
+            terms.to_json(),
+            serde_json::json!({
+                "view": "profileComments",
+                "userId": "nmk3nLpQE89dMRzzN",
+                "after": "2020-01-01T00:00:00+00:00",
+                "limit": 10
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_all_comments() {
+        let fixture = FakeResponse::fixture_json("comments_query.json");
+        let results = fixture["data"]["comments"]["results"].as_array().unwrap();
+        let page = |range: std::ops::Range<usize>| {
+            FakeResponse::data(serde_json::json!({ "comments": { "results": results[range] } }))
+        };
+
+        let server = FakeServer::start().await;
+        server.respond(
+            "PostCommentCountQuery",
+            FakeResponse::data(serde_json::json!({ "post": { "result": { "commentCount": 5 } } })),
+        );
+        // the second comment moves to the second page while paging
+        // once for `get_all_comments` and once for `all_comments_stream`
+        for _ in 0..2 {
+            for response in [page(0..2), page(1..3), page(3..4)] {
+                server.respond_once("CommentsQuery", response);
+            }
+        }
+        let client = server.client();
+        let post_id: PostId = "7ZqGiPHTpiDMwqMN2".parse().unwrap();
+        let options = PagingOptions::default().page_size(2);
+
+        let all = client.get_all_comments(&post_id, options).await.unwrap();
+        assert_eq!(all.comment_count, 5);
+        assert_eq!(all.received, 4);
+        assert!(!all.is_complete());
+        // the fixture's deleted comment is received but skipped
+        assert_eq!(all.comments.len(), 3);
+
+        let streamed: Vec<_> = client
+            .all_comments_stream(&post_id, options)
+            .map(|comment| comment.unwrap().id)
+            .collect()
+            .await;
+        assert_eq!(streamed.len(), 3);
+        assert_eq!(streamed[0], "aHZbWCe6cZq4uCQQq");
+
+        let requests = server.requests();
+        let offsets: Vec<_> = requests
+            .iter()
+            .filter(|request| request["operationName"] == "CommentsQuery")
+            .map(|request| request["variables"]["terms"]["offset"].clone())
+            .collect();
+        assert_eq!(offsets, [0, 2, 4, 0, 2, 4]);
+        assert_eq!(requests[1]["variables"]["terms"]["view"], "postCommentsOld");
+    }
+
+    #[tokio::test]
+    async fn test_get_all_comments_concurrently() {
+        // the third comment repeats the second, so the first two pages overlap
+        let comments: Vec<JSON> = ["a", "b", "b", "c", "d", "e", "f"]
+            .iter()
+            .enumerate()
+            .map(|(i, id)| {
+                serde_json::json!({
+                    "_id": id.repeat(17),
+                    "pageUrl": format!("/posts/7ZqGiPHTpiDMwqMN2?commentId={}", id.repeat(17)),
+                    "postedAt": "2020-01-01T00:00:00.000Z",
+                    "baseScore": i as f64,
+                    "voteCount": 1.0,
+                    "htmlBody": "<p>hi</p>",
+                    "contents": { "markdown": "hi" }
+                })
+            })
+            .collect();
+
+        let server = FakeServer::start().await;
+        server.respond(
+            "PostCommentCountQuery",
+            FakeResponse::data(serde_json::json!({ "post": { "result": { "commentCount": 7 } } })),
+        );
+        server.respond_with("CommentsQuery", move |request| {
+            let terms = &request["variables"]["terms"];
+            let offset = terms["offset"].as_u64().unwrap() as usize;
+            let limit = terms["limit"].as_u64().unwrap() as usize;
+            let page = &comments[offset.min(7)..(offset + limit).min(7)];
+            FakeResponse::data(serde_json::json!({ "comments": { "results": page } }))
+        });
+
+        let options = PagingOptions::default().page_size(2).concurrency(3);
+        let all = server
+            .client()
+            .get_all_comments(&"7ZqGiPHTpiDMwqMN2".parse().unwrap(), options)
+            .await
+            .unwrap();
+        assert_eq!(all.received, 6);
+        assert_eq!(all.comments.len(), 6);
+        assert_eq!(all.comments.position(&"f".repeat(17)), Some(0));
+
+        // three pages at once up to the comment count, then the last one
+        let mut offsets: Vec<_> = server
+            .requests()
+            .iter()
+            .filter(|request| request["operationName"] == "CommentsQuery")
+            .map(|request| request["variables"]["terms"]["offset"].as_u64().unwrap())
+            .collect();
+        assert_eq!(offsets.len(), 4);
+        offsets[..3].sort_unstable();
+        assert_eq!(offsets, [0, 2, 4, 6]);
+    }
+}
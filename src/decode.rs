@@ -0,0 +1,83 @@
+use crate::Error;
+
+/// How the client handles posts with missing fields.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DecodingPolicy {
+    /// Fail with `Error::MalformattedResponse` if any field is missing.
+    #[default]
+    Strict,
+    /// Fill missing fields with their default value and list them in `Post::missing_fields`.
+    /// Link posts, events and some imported posts lack a body, slug or word count.
+    Lenient,
+}
+
+/// Which comments the client returns besides the regular ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CommentFilter {
+    /// Skip deleted comments and comments without a body.
+    #[default]
+    Drop,
+    /// Keep them so that their replies keep their parent, with the content of deleted comments
+    /// removed. See [`crate::Comment::tombstone_text`] for what the site shows instead.
+    Tombstone,
+    /// Keep them with whatever content the server returns.
+    Include,
+}
+
+/// Which fields are requested for posts and comments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FieldSet {
+    /// The commonly needed fields. The others are `None`.
+    #[default]
+    Standard,
+    /// Also the rarely needed fields, documented as extended on `Post` and `Comment`.
+    Extended,
+}
+
+/// Unwraps the optional fields of a response according to the decoding policy.
+pub(crate) struct FieldDecoder {
+    policy: DecodingPolicy,
+    id: Option<String>,
+    pub(crate) missing_fields: Vec<String>,
+}
+
+impl FieldDecoder {
+    pub(crate) fn new(policy: DecodingPolicy, id: Option<String>) -> Self {
+        Self {
+            policy,
+            id,
+            missing_fields: Vec::new(),
+        }
+    }
+
+    pub(crate) fn field<T: Default>(
+        &mut self,
+        value: Option<T>,
+        field: &'static str,
+    ) -> Result<T, Error> {
+        match (value, self.policy) {
+            (Some(value), _) => Ok(value),
+            (None, DecodingPolicy::Lenient) => {
+                self.missing_fields.push(field.to_string());
+                Ok(T::default())
+            }
+            (None, DecodingPolicy::Strict) => Err(Error::MalformattedResponse {
+                field,
+                id: self.id.clone(),
+            }),
+        }
+    }
+
+    /// An optional ID, which is treated like a missing field if it is malformed.
+    pub(crate) fn id<T>(
+        &mut self,
+        value: Option<String>,
+        parse: impl FnOnce(String) -> Result<T, Error>,
+        field: &'static str,
+    ) -> Result<Option<T>, Error> {
+        match value.map(parse).transpose() {
+            Ok(id) => Ok(id),
+            Err(_) => self.field(None::<Option<T>>, field),
+        }
+    }
+}
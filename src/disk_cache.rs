@@ -0,0 +1,449 @@
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::{CommentSet, Error, Post, PostId, Site};
+
+/// Bumped whenever `Post` or `Comment` change so that stale entries are refetched.
+const SCHEMA_VERSION: u32 = 1;
+
+/// When the disk cache is consulted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CachePolicy {
+    /// Never sends requests, fails with `Error::NotCached` for anything not in the cache.
+    /// Only posts fetched by ID and comments are cached, every other call fails.
+    OfflineOnly,
+    /// Uses cached entries younger than `max_age`, fetches and caches everything else.
+    #[default]
+    CacheFirst,
+    /// Always fetches, and only falls back to the cache if the request fails.
+    NetworkFirst,
+}
+
+/// A disk-backed cache of decoded posts and comments, enabled with the `disk-cache` feature.
+///
+/// Entries are stored as JSON under `<dir>/<site>/posts/<post_id>.json` and
+/// `<dir>/<site>/comments/<post_id>.json`, together with the time they were fetched at. `<site>`
+/// is the host of the site, e.g. `www.lesswrong.com`, so that clients of different sites can
+/// share a directory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiskCache {
+    dir: PathBuf,
+    policy: CachePolicy,
+    max_age: Option<Duration>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheEntry<T> {
+    schema_version: u32,
+    fetched_at: DateTime<Utc>,
+    /// The `limit` comments were fetched with, unused for posts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    limit: Option<i64>,
+    value: T,
+}
+
+impl DiskCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            policy: CachePolicy::default(),
+            max_age: None,
+        }
+    }
+
+    pub fn policy(mut self, policy: CachePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Entries older than `max_age` are refetched with `CachePolicy::CacheFirst`.
+    /// They never expire by default.
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub(crate) fn is_offline(&self) -> bool {
+        self.policy == CachePolicy::OfflineOnly
+    }
+
+    /// When the post was last fetched from `site`, with any `FieldSet` or `DecodingPolicy`,
+    /// if it is cached.
+    pub fn post_fetched_at(&self, site: &Site, post_id: &PostId) -> Option<DateTime<Utc>> {
+        let prefix = format!("{}.", post_id);
+        std::fs::read_dir(self.site_dir(site).join("posts"))
+            .ok()?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| {
+                let name = entry.file_name();
+                let name = name.to_string_lossy();
+                name.starts_with(&prefix) && name.ends_with(".json")
+            })
+            .filter_map(|entry| read::<Post>(&entry.path()))
+            .map(|entry| entry.fetched_at)
+            .max()
+    }
+
+    pub(crate) async fn post<F, Fut>(
+        &self,
+        site: &Site,
+        post_id: &PostId,
+        variant: &str,
+        fetch: F,
+    ) -> Result<Post, Error>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Post, Error>>,
+    {
+        let path = self.post_path(site, post_id, variant);
+        self.get_or_fetch(&path, None, |_| true, fetch).await
+    }
+
+    pub(crate) async fn comments<F, Fut>(
+        &self,
+        site: &Site,
+        post_id: &PostId,
+        limit: i64,
+        variant: &str,
+        fetch: F,
+    ) -> Result<CommentSet, Error>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<CommentSet, Error>>,
+    {
+        // comments fetched with a larger limit can be reused if they are all the post has
+        let is_usable = |entry: &CacheEntry<CommentSet>| {
+            let cached_limit = entry.limit.unwrap_or_default();
+            cached_limit == limit || (cached_limit >= limit && entry.value.len() as i64 <= limit)
+        };
+        let path = self.comments_path(site, post_id, variant);
+        self.get_or_fetch(&path, Some(limit), is_usable, fetch)
+            .await
+    }
+
+    async fn get_or_fetch<T, U, F, Fut>(
+        &self,
+        path: &Path,
+        limit: Option<i64>,
+        is_usable: U,
+        fetch: F,
+    ) -> Result<T, Error>
+    where
+        T: Serialize + DeserializeOwned,
+        U: Fn(&CacheEntry<T>) -> bool,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, Error>>,
+    {
+        let cached = read::<T>(path).filter(|entry| is_usable(entry));
+
+        match self.policy {
+            CachePolicy::OfflineOnly => {
+                return cached
+                    .map(|entry| entry.value)
+                    .ok_or_else(|| Error::NotCached(path.display().to_string()));
+            }
+            CachePolicy::CacheFirst => {
+                if let Some(entry) = cached.filter(|entry| !self.is_expired(entry)) {
+                    return Ok(entry.value);
+                }
+            }
+            CachePolicy::NetworkFirst => {
+                return match fetch().await {
+                    Ok(value) => {
+                        let _ = write(path, limit, &value);
+                        Ok(value)
+                    }
+                    // a post that does not exist (anymore) should not be served from the cache
+                    Err(Error::NotFound) => Err(Error::NotFound),
+                    Err(e) => cached.map(|entry| entry.value).ok_or(e),
+                };
+            }
+        }
+
+        let value = fetch().await?;
+        // a failed cache write does not fail the fetch, the entry is fetched again next time
+        let _ = write(path, limit, &value);
+        Ok(value)
+    }
+
+    fn is_expired<T>(&self, entry: &CacheEntry<T>) -> bool {
+        match self.max_age {
+            Some(max_age) => (Utc::now() - entry.fetched_at)
+                .to_std()
+                .is_ok_and(|age| age > max_age),
+            None => false,
+        }
+    }
+
+    /// The host of the site, with the port and path of custom sites, e.g. `127.0.0.1_3000`.
+    fn site_dir(&self, site: &Site) -> PathBuf {
+        let url = site.base_url();
+        let host = url.split_once("://").map_or(url, |(_, host)| host);
+        let name: String = host
+            .chars()
+            .map(|c| match c {
+                'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' => c,
+                _ => '_',
+            })
+            .collect();
+        self.dir.join(name)
+    }
+
+    /// Posts fetched with `FieldSet::Extended` or `DecodingPolicy::Lenient` are stored
+    /// separately, e.g. in `<post_id>.extended.lenient.json`.
+    fn post_path(&self, site: &Site, post_id: &PostId, variant: &str) -> PathBuf {
+        self.site_dir(site)
+            .join("posts")
+            .join(format!("{}{}.json", post_id, variant))
+    }
+
+    /// Comments fetched with another `CommentFilter`, `FieldSet` or `DecodingPolicy` than the
+    /// default are stored separately, e.g. in `<post_id>.tombstone.extended.json`.
+    fn comments_path(&self, site: &Site, post_id: &PostId, variant: &str) -> PathBuf {
+        self.site_dir(site)
+            .join("comments")
+            .join(format!("{}{}.json", post_id, variant))
+    }
+}
+
+/// Reads a cache entry, treating unreadable entries and entries of another schema version as missing.
+fn read<T: DeserializeOwned>(path: &Path) -> Option<CacheEntry<T>> {
+    let json = std::fs::read_to_string(path).ok()?;
+    let entry: CacheEntry<T> = serde_json::from_str(&json).ok()?;
+    (entry.schema_version == SCHEMA_VERSION).then_some(entry)
+}
+
+fn write<T: Serialize>(path: &Path, limit: Option<i64>, value: &T) -> Result<(), Error> {
+    let entry = CacheEntry {
+        schema_version: SCHEMA_VERSION,
+        fetched_at: Utc::now(),
+        limit,
+        value,
+    };
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    // write to a temporary file first so that an interrupted write never leaves a corrupt entry,
+    // unique per write as concurrent fetches of the same entry write it at the same time
+    static WRITES: AtomicU64 = AtomicU64::new(0);
+    let tmp = path.with_extension(format!(
+        "json.{}-{}.tmp",
+        std::process::id(),
+        WRITES.fetch_add(1, Ordering::Relaxed)
+    ));
+    let json = serde_json::to_string(&entry)?;
+    let result = std::fs::write(&tmp, json).and_then(|()| std::fs::rename(&tmp, path));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp);
+    }
+    Ok(result?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{FakeResponse, FakeServer};
+    use crate::DecodingPolicy;
+
+    #[tokio::test]
+    async fn test_disk_cache_policies() {
+        let dir =
+            std::env::temp_dir().join(format!("lesswrong-api-disk-cache-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let server = FakeServer::start().await;
+        server.respond("PostQuery", FakeResponse::fixture("post_query.json"));
+        let client = |policy| {
+            server
+                .client_builder()
+                .disk_cache(DiskCache::new(&dir).policy(policy))
+                .build()
+                .unwrap()
+        };
+
+        let post_id: PostId = "7ZqGiPHTpiDMwqMN2".parse().unwrap();
+        let offline = client(CachePolicy::OfflineOnly);
+        assert!(matches!(
+            offline.get_post(&post_id).await,
+            Err(Error::NotCached(_))
+        ));
+
+        let post = client(CachePolicy::CacheFirst)
+            .get_post(&post_id)
+            .await
+            .unwrap();
+        assert_eq!(server.requests().len(), 1);
+        assert_eq!(offline.get_post(&post_id).await.unwrap(), post);
+        assert!(DiskCache::new(&dir)
+            .post_fetched_at(offline.site(), &post_id)
+            .is_some());
+
+        // calls the disk cache does not cover never reach the server either
+        assert!(matches!(
+            offline.get_comments_lenient(&post_id, 10).await,
+            Err(Error::NotCached(_))
+        ));
+        assert!(matches!(
+            offline.get_posts(std::slice::from_ref(&post_id)).await[&post_id],
+            Err(Error::BatchFailed(_))
+        ));
+        assert_eq!(server.requests().len(), 1);
+
+        // network-first falls back to the cache when the server fails
+        server.respond(
+            "PostQuery",
+            FakeResponse::Status(reqwest::StatusCode::BAD_GATEWAY, String::new()),
+        );
+        let network_first = client(CachePolicy::NetworkFirst);
+        assert_eq!(network_first.get_post(&post_id).await.unwrap(), post);
+        assert_eq!(server.requests().len(), 2);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_disk_cache_keys() {
+        let dir = std::env::temp_dir().join(format!(
+            "lesswrong-api-disk-cache-keys-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let server = FakeServer::start().await;
+        server.respond("PostQuery", FakeResponse::fixture("post_query.json"));
+        let client = |policy| {
+            server
+                .client_builder()
+                .decoding_policy(policy)
+                .disk_cache(DiskCache::new(&dir))
+                .build()
+                .unwrap()
+        };
+        let post_id: PostId = "7ZqGiPHTpiDMwqMN2".parse().unwrap();
+
+        // a leniently decoded entry may lack fields, a strict client fetches the post again
+        let lenient = client(DecodingPolicy::Lenient);
+        lenient.get_post(&post_id).await.unwrap();
+        assert!(DiskCache::new(&dir)
+            .post_fetched_at(lenient.site(), &post_id)
+            .is_some());
+        client(DecodingPolicy::Strict)
+            .get_post(&post_id)
+            .await
+            .unwrap();
+        assert_eq!(server.requests().len(), 2);
+        lenient.get_post(&post_id).await.unwrap();
+        assert_eq!(server.requests().len(), 2);
+
+        // another site does not see the entries
+        assert!(DiskCache::new(&dir)
+            .post_fetched_at(&Site::EaForum, &post_id)
+            .is_none());
+        let other = FakeServer::start().await;
+        other.respond("PostQuery", FakeResponse::fixture("post_query.json"));
+        other
+            .client_builder()
+            .disk_cache(DiskCache::new(&dir))
+            .build()
+            .unwrap()
+            .get_post(&post_id)
+            .await
+            .unwrap();
+        assert_eq!(other.requests().len(), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_disk_cache_comment_limits() {
+        let dir = std::env::temp_dir().join(format!(
+            "lesswrong-api-disk-cache-limits-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let server = FakeServer::start().await;
+        server.respond(
+            "CommentsQuery",
+            FakeResponse::fixture("comments_query.json"),
+        );
+        let client = server
+            .client_builder()
+            .disk_cache(DiskCache::new(&dir))
+            .build()
+            .unwrap();
+        let post_id: PostId = "7ZqGiPHTpiDMwqMN2".parse().unwrap();
+
+        // the post has 3 visible comments
+        let comments = client.get_comments(&post_id, 100).await.unwrap();
+        assert_eq!(comments.len(), 3);
+        // all of them fit a smaller limit, the cached comments are reused
+        assert_eq!(client.get_comments(&post_id, 10).await.unwrap(), comments);
+        assert_eq!(server.requests().len(), 1);
+        // a limit below the number of comments, or above the cached limit, is refetched
+        client.get_comments(&post_id, 2).await.unwrap();
+        assert_eq!(server.requests().len(), 2);
+        client.get_comments(&post_id, 200).await.unwrap();
+        assert_eq!(server.requests().len(), 3);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_disk_cache_writes() {
+        let dir = std::env::temp_dir().join(format!(
+            "lesswrong-api-disk-cache-writes-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("posts").join("7ZqGiPHTpiDMwqMN2.json");
+
+        // concurrent writes of the same entry do not interfere
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    for _ in 0..20 {
+                        write(&path, None, &"value").unwrap();
+                    }
+                });
+            }
+        });
+        assert_eq!(read::<String>(&path).unwrap().value, "value");
+        assert_eq!(
+            std::fs::read_dir(path.parent().unwrap()).unwrap().count(),
+            1
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_disk_cache_write_failure() {
+        // a file where the cache directory should be makes every write fail
+        let dir = std::env::temp_dir().join(format!(
+            "lesswrong-api-disk-cache-unwritable-{}",
+            std::process::id()
+        ));
+        std::fs::write(&dir, "").unwrap();
+
+        let server = FakeServer::start().await;
+        server.respond("PostQuery", FakeResponse::fixture("post_query.json"));
+        let client = server
+            .client_builder()
+            .disk_cache(DiskCache::new(&dir))
+            .build()
+            .unwrap();
+        let post_id: PostId = "7ZqGiPHTpiDMwqMN2".parse().unwrap();
+        assert_eq!(client.get_post(&post_id).await.unwrap().id, post_id);
+
+        std::fs::remove_file(&dir).unwrap();
+    }
+}
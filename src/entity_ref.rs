@@ -0,0 +1,199 @@
+use reqwest::Url;
+use std::str::FromStr;
+
+use crate::{CommentId, Error, PostId, SequenceId};
+
+/// What a LessWrong (or other ForumMagnum site) URL points to.
+///
+/// ```
+/// # use lesswrong_api::EntityRef;
+/// let entity: EntityRef = "https://www.lesswrong.com/s/7gRSERQZbqTuLX5re/p/7ZqGiPHTpiDMwqMN2"
+///     .parse()
+///     .unwrap();
+/// assert_eq!(
+///     entity,
+///     EntityRef::Post {
+///         id: "7ZqGiPHTpiDMwqMN2".parse().unwrap(),
+///         slug: None,
+///         sequence_id: Some("7gRSERQZbqTuLX5re".parse().unwrap()),
+///     }
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntityRef {
+    /// `/posts/<id>/<slug>`, `/s/<sequence_id>/p/<id>`, `/events/<id>/<slug>`, `/g/<group>/p/<id>`
+    Post {
+        id: PostId,
+        slug: Option<String>,
+        sequence_id: Option<SequenceId>,
+    },
+    /// Posts in curated collections, e.g. `/rationality/<slug>` or `/hpmor/<slug>`.
+    PostSlug { slug: String },
+    /// `/posts/<post_id>/<slug>?commentId=<id>`
+    Comment { post_id: PostId, id: CommentId },
+    /// `/s/<id>` or `/sequences/<id>`
+    Sequence { id: SequenceId },
+    /// `/tag/<slug>`, `/topics/<slug>` or `/w/<slug>`
+    Tag { slug: String },
+    /// `/users/<slug>`
+    User { slug: String },
+    /// `/lw/<legacy_id>/<slug>` links of the pre-2018 LessWrong. The base 36 ID of the URL is
+    /// converted to the decimal `legacyId` stored by the server.
+    LegacyPost { legacy_id: u64 },
+    /// `/lw/<post_legacy_id>/<slug>/<legacy_id>`
+    LegacyComment { post_legacy_id: u64, legacy_id: u64 },
+}
+
+// collections whose posts are linked by slug only
+const COLLECTIONS: &[&str] = &["rationality", "codex", "hpmor", "highlights"];
+
+impl EntityRef {
+    /// Parses an absolute URL of any site, a URL without scheme (`lesswrong.com/posts/...`)
+    /// or a path (`/posts/...`). IDs in the URL are validated, see [`PostId`].
+    pub fn parse(url: &str) -> Result<Self, Error> {
+        let invalid = || Error::InvalidUrl(url.to_string());
+
+        let trimmed = url.trim();
+        let parsed = if trimmed.starts_with('/') {
+            Url::parse("https://www.lesswrong.com")
+                .and_then(|base| base.join(trimmed))
+                .map_err(|_| invalid())?
+        } else if trimmed.contains("://") {
+            Url::parse(trimmed).map_err(|_| invalid())?
+        } else {
+            Url::parse(&format!("https://{}", trimmed)).map_err(|_| invalid())?
+        };
+
+        let segments: Vec<&str> = parsed
+            .path_segments()
+            .map(|segments| segments.filter(|s| !s.is_empty()).collect())
+            .unwrap_or_default();
+        let comment_id = parsed
+            .query_pairs()
+            .find(|(key, _)| key == "commentId")
+            .map(|(_, value)| value.into_owned());
+        let owned = |s: &str| s.to_string();
+
+        let post = |id: &str, slug: Option<&str>, sequence_id: Option<&str>| {
+            Ok::<_, Error>(match &comment_id {
+                Some(comment_id) => EntityRef::Comment {
+                    post_id: id.parse()?,
+                    id: comment_id.parse()?,
+                },
+                None => EntityRef::Post {
+                    id: id.parse()?,
+                    slug: slug.map(owned),
+                    sequence_id: sequence_id.map(str::parse).transpose()?,
+                },
+            })
+        };
+
+        Ok(match segments.as_slice() {
+            ["posts", id, rest @ ..] | ["events", id, rest @ ..] => {
+                post(id, rest.first().copied(), None)?
+            }
+            ["s", sequence_id, "p", id, ..] => post(id, None, Some(sequence_id))?,
+            ["g", _, "p", id, ..] => post(id, None, None)?,
+            ["s", id] | ["sequences", id] => EntityRef::Sequence { id: id.parse()? },
+            ["tag", slug, ..] | ["topics", slug, ..] | ["w", slug, ..] => {
+                EntityRef::Tag { slug: owned(slug) }
+            }
+            ["users", slug, ..] => EntityRef::User { slug: owned(slug) },
+            ["lw", post_legacy_id, _, legacy_id, ..] => EntityRef::LegacyComment {
+                post_legacy_id: base36(post_legacy_id).ok_or_else(invalid)?,
+                legacy_id: base36(legacy_id).ok_or_else(invalid)?,
+            },
+            ["lw", legacy_id, ..] => EntityRef::LegacyPost {
+                legacy_id: base36(legacy_id).ok_or_else(invalid)?,
+            },
+            [collection, slug] if COLLECTIONS.contains(collection) => {
+                EntityRef::PostSlug { slug: owned(slug) }
+            }
+            _ => return Err(invalid()),
+        })
+    }
+}
+
+impl FromStr for EntityRef {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        EntityRef::parse(s)
+    }
+}
+
+fn base36(s: &str) -> Option<u64> {
+    u64::from_str_radix(s, 36).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_urls() {
+        let parse = |url| EntityRef::parse(url).unwrap();
+
+        assert_eq!(
+            parse(
+                "https://www.lesswrong.com/posts/7ZqGiPHTpiDMwqMN2/twelve-virtues-of-rationality"
+            ),
+            EntityRef::Post {
+                id: "7ZqGiPHTpiDMwqMN2".parse().unwrap(),
+                slug: Some("twelve-virtues-of-rationality".to_string()),
+                sequence_id: None,
+            }
+        );
+        assert_eq!(
+            parse(
+                "lesswrong.com/posts/7ZqGiPHTpiDMwqMN2/twelve-virtues?commentId=aHZbWCe6cZq4uCQQq"
+            ),
+            EntityRef::Comment {
+                post_id: "7ZqGiPHTpiDMwqMN2".parse().unwrap(),
+                id: "aHZbWCe6cZq4uCQQq".parse().unwrap(),
+            }
+        );
+        assert_eq!(
+            parse("/s/7gRSERQZbqTuLX5re"),
+            EntityRef::Sequence {
+                id: "7gRSERQZbqTuLX5re".parse().unwrap()
+            }
+        );
+        assert_eq!(
+            parse("https://www.lesswrong.com/w/rationality"),
+            EntityRef::Tag {
+                slug: "rationality".to_string()
+            }
+        );
+        assert_eq!(
+            parse("https://www.alignmentforum.org/users/eliezer_yudkowsky"),
+            EntityRef::User {
+                slug: "eliezer_yudkowsky".to_string()
+            }
+        );
+        assert_eq!(
+            parse("https://www.lesswrong.com/rationality/the-simple-truth"),
+            EntityRef::PostSlug {
+                slug: "the-simple-truth".to_string()
+            }
+        );
+        assert_eq!(
+            parse("http://lesswrong.com/lw/2q/the_proper_use_of_humility/"),
+            EntityRef::LegacyPost { legacy_id: 98 }
+        );
+        assert_eq!(
+            parse("/lw/2q/the_proper_use_of_humility/1a"),
+            EntityRef::LegacyComment {
+                post_legacy_id: 98,
+                legacy_id: 46
+            }
+        );
+
+        assert!(EntityRef::parse("https://www.lesswrong.com/allPosts").is_err());
+        assert!(EntityRef::parse("/lw/not-base36!/slug").is_err());
+        assert!(matches!(
+            EntityRef::parse("/posts/123456/slug"),
+            Err(Error::InvalidId { kind: "post", .. })
+        ));
+    }
+}
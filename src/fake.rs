@@ -0,0 +1,173 @@
+use async_trait::async_trait;
+use futures::stream::{self, BoxStream, StreamExt};
+use std::collections::HashMap;
+
+use crate::{Comment, CommentSet, CommentsTerms, Error, LessWrongApi, Post, PostId, PostsQuery};
+
+/// An in-memory [`LessWrongApi`] serving the posts and comments it was seeded with.
+///
+/// ```
+/// # use lesswrong_api::{FakeLessWrong, LessWrongApi, Post};
+/// # #[tokio::main]
+/// # async fn main() {
+/// let api = FakeLessWrong::new().with_post(Post {
+///     id: "7ZqGiPHTpiDMwqMN2".parse().unwrap(),
+///     title: "Twelve Virtues of Rationality".to_string(),
+///     ..Default::default()
+/// });
+/// let post = api.get_post(&"7ZqGiPHTpiDMwqMN2".parse().unwrap()).await.unwrap();
+/// assert_eq!(post.title, "Twelve Virtues of Rationality");
+/// # }
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct FakeLessWrong {
+    posts: HashMap<PostId, Post>,
+    comments: HashMap<PostId, Vec<Comment>>,
+}
+
+impl FakeLessWrong {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_post(mut self, post: Post) -> Self {
+        self.insert_post(post);
+        self
+    }
+
+    pub fn with_comments(
+        mut self,
+        post_id: PostId,
+        comments: impl IntoIterator<Item = Comment>,
+    ) -> Self {
+        for comment in comments {
+            self.insert_comment(&post_id, comment);
+        }
+        self
+    }
+
+    pub fn insert_post(&mut self, post: Post) {
+        self.posts.insert(post.id.clone(), post);
+    }
+
+    pub fn insert_comment(&mut self, post_id: &PostId, comment: Comment) {
+        let comments = self.comments.entry(post_id.clone()).or_default();
+        comments.retain(|c| c.id != comment.id);
+        comments.push(comment);
+    }
+}
+
+#[async_trait]
+impl LessWrongApi for FakeLessWrong {
+    async fn get_post(&self, post_id: &PostId) -> Result<Post, Error> {
+        self.posts.get(post_id).cloned().ok_or(Error::NotFound)
+    }
+
+    async fn get_post_by_slug(&self, slug: &str) -> Result<Post, Error> {
+        self.posts
+            .values()
+            .find(|post| post.slug == slug)
+            .cloned()
+            .ok_or(Error::NotFound)
+    }
+
+    /// Returns the highest-scored comments first, like the `postCommentsTop` view.
+    async fn get_comments(&self, post_id: &PostId, limit: i64) -> Result<CommentSet, Error> {
+        let mut comments = self.comments.get(post_id).cloned().unwrap_or_default();
+        comments.sort_by(|a, b| b.base_score.total_cmp(&a.base_score));
+        Ok(comments.into_iter().take(limit.max(0) as usize).collect())
+    }
+
+    /// Lists the seeded posts matching the filters of the query, in the order of its view.
+    fn list_posts(&self, query: PostsQuery) -> BoxStream<'static, Result<Post, Error>> {
+        let posts = query.apply(self.posts.values());
+        stream::iter(posts.into_iter().map(Ok)).boxed()
+    }
+
+    async fn query_comments(&self, terms: &CommentsTerms) -> Result<CommentSet, Error> {
+        Ok(
+            terms.apply(self.comments.iter().flat_map(|(post_id, comments)| {
+                comments.iter().map(move |comment| (post_id, comment))
+            })),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_fake_lesswrong() {
+        let comment = |id: &str, base_score| Comment {
+            id: id.parse().unwrap(),
+            base_score,
+            ..Default::default()
+        };
+        let post_id: PostId = "7ZqGiPHTpiDMwqMN2".parse().unwrap();
+        let api: Box<dyn LessWrongApi> = Box::new(
+            FakeLessWrong::new()
+                .with_post(Post {
+                    id: post_id.clone(),
+                    base_score: 10.0,
+                    ..Default::default()
+                })
+                .with_post(Post {
+                    id: "QuestionPst234567".parse().unwrap(),
+                    base_score: 20.0,
+                    question: Some(true),
+                    ..Default::default()
+                })
+                .with_comments(
+                    post_id.clone(),
+                    [
+                        comment("aaaaaaaaaaaaaaaaa", 1.0),
+                        comment("bbbbbbbbbbbbbbbbb", 5.0),
+                        comment("ccccccccccccccccc", 3.0),
+                    ],
+                ),
+        );
+
+        assert_eq!(api.get_post(&post_id).await.unwrap().id, post_id);
+        assert!(matches!(
+            api.get_post(&"MissingPst2345678".parse().unwrap()).await,
+            Err(Error::NotFound)
+        ));
+        assert!(matches!(
+            api.get_post_by_legacy_id(1).await,
+            Err(Error::Unsupported("get_post_by_legacy_id"))
+        ));
+
+        let comments = api.get_comments(&post_id, 2).await.unwrap();
+        let ids: Vec<_> = comments.ids().map(|id| id.as_str()).collect();
+        assert_eq!(ids, ["bbbbbbbbbbbbbbbbb", "ccccccccccccccccc"]);
+
+        let posts: Vec<_> = api
+            .list_posts(PostsQuery::new(crate::PostsView::Top))
+            .map(|post| post.unwrap().id.into_inner())
+            .collect()
+            .await;
+        assert_eq!(posts, ["QuestionPst234567", "7ZqGiPHTpiDMwqMN2"]);
+        let posts: Vec<_> = api
+            .list_posts(PostsQuery::default().question(false))
+            .collect()
+            .await;
+        assert_eq!(posts.len(), 1);
+
+        let comments = api
+            .query_comments(&CommentsTerms::for_post(post_id.clone()).offset(1).limit(1))
+            .await
+            .unwrap();
+        assert!(comments.contains("ccccccccccccccccc") && comments.len() == 1);
+
+        let options = crate::PagingOptions::default();
+        assert_eq!(
+            api.get_all_comments(&post_id, options)
+                .await
+                .unwrap()
+                .comment_count,
+            3
+        );
+        assert_eq!(api.all_comments_stream(&post_id, options).count().await, 3);
+    }
+}
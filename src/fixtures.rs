@@ -0,0 +1,82 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::{Error, JSON};
+
+/// Records the responses of all GraphQL requests to a directory, or serves them back from it
+/// without touching the network. Each fixture is a JSON file named after the operation and a
+/// hash of its variables, e.g. `PostQuery-5d2c0e8f3a1b4c67.json`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FixtureMode {
+    /// Sends requests as usual and writes every response with status 200 to the directory.
+    /// That includes responses with GraphQL `errors`, e.g. for a post that does not exist,
+    /// so that replaying them fails the same way. HTTP errors are not recorded.
+    Record(PathBuf),
+    /// Answers requests from the directory only, failing with `Error::MissingFixture` for
+    /// requests that were never recorded.
+    Replay(PathBuf),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Fixture {
+    #[serde(rename = "operationName")]
+    operation_name: String,
+    variables: JSON,
+    response: JSON,
+}
+
+impl FixtureMode {
+    fn dir(&self) -> &Path {
+        match self {
+            FixtureMode::Record(dir) | FixtureMode::Replay(dir) => dir,
+        }
+    }
+
+    pub(crate) fn load(&self, body: &JSON) -> Result<JSON, Error> {
+        let (operation_name, variables) = operation(body);
+        let path = self.path(&operation_name, &variables);
+        let json = std::fs::read_to_string(&path).map_err(|_| Error::MissingFixture {
+            operation: operation_name,
+            path: path.clone(),
+        })?;
+        let fixture: Fixture = serde_json::from_str(&json)?;
+        Ok(fixture.response)
+    }
+
+    pub(crate) fn store(&self, body: &JSON, response: &JSON) -> Result<(), Error> {
+        let (operation_name, variables) = operation(body);
+        let path = self.path(&operation_name, &variables);
+        let fixture = Fixture {
+            operation_name,
+            variables,
+            response: response.clone(),
+        };
+        std::fs::create_dir_all(self.dir())?;
+        std::fs::write(path, serde_json::to_string_pretty(&fixture)?)?;
+        Ok(())
+    }
+
+    fn path(&self, operation_name: &str, variables: &JSON) -> PathBuf {
+        // serde_json sorts object keys, so the serialized variables are stable
+        let hash = fnv1a(variables.to_string().as_bytes());
+        self.dir()
+            .join(format!("{}-{:016x}.json", operation_name, hash))
+    }
+}
+
+pub(crate) fn operation(body: &JSON) -> (String, JSON) {
+    (
+        body["operationName"]
+            .as_str()
+            .unwrap_or("anonymous")
+            .to_string(),
+        body["variables"].clone(),
+    )
+}
+
+// a hash that stays the same across Rust releases, fixture names end up in version control
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
@@ -0,0 +1,167 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use std::borrow::Borrow;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::Error;
+
+/// Length of the IDs ForumMagnum generates for its documents.
+const ID_LENGTH: usize = 17;
+/// The alphabet of those IDs, leaving out characters that are easy to confuse (`0`/`O`, `1`/`l`, ...).
+const ID_ALPHABET: &str = "23456789ABCDEFGHJKLMNPQRSTWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// Documents created by imports, such as the tags of the first tagging release, have hexadecimal
+/// MongoDB object IDs instead, e.g. `5f5c37ee1b5cdee568cfb2ac`.
+const OBJECT_ID_LENGTH: usize = 24;
+
+fn is_valid_id(id: &str) -> bool {
+    (id.len() == ID_LENGTH && id.chars().all(|c| ID_ALPHABET.contains(c)))
+        || (id.len() == OBJECT_ID_LENGTH && id.chars().all(|c| matches!(c, '0'..='9' | 'a'..='f')))
+}
+
+macro_rules! id_type {
+    ($(#[$meta:meta])* $name:ident, $kind:literal) => {
+        $(#[$meta])*
+        ///
+        /// The `Default` is an empty placeholder, which is not a valid ID.
+        #[derive(Debug, Default, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+        #[serde(transparent)]
+        pub struct $name(String);
+
+        impl $name {
+            /// Validates that `id` is a ForumMagnum document ID.
+            pub fn new(id: impl Into<String>) -> Result<Self, Error> {
+                let id = id.into();
+                if is_valid_id(&id) {
+                    Ok(Self(id))
+                } else {
+                    Err(Error::InvalidId { kind: $kind, id })
+                }
+            }
+
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+
+            pub fn into_inner(self) -> String {
+                self.0
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = Error;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                Self::new(s)
+            }
+        }
+
+        impl TryFrom<&str> for $name {
+            type Error = Error;
+
+            fn try_from(s: &str) -> Result<Self, Self::Error> {
+                Self::new(s)
+            }
+        }
+
+        impl TryFrom<String> for $name {
+            type Error = Error;
+
+            fn try_from(s: String) -> Result<Self, Self::Error> {
+                Self::new(s)
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
+
+        impl AsRef<str> for $name {
+            fn as_ref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        // allows looking up maps keyed by ID with a `&str`
+        impl Borrow<str> for $name {
+            fn borrow(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl PartialEq<str> for $name {
+            fn eq(&self, other: &str) -> bool {
+                self.0 == other
+            }
+        }
+
+        impl PartialEq<&str> for $name {
+            fn eq(&self, other: &&str) -> bool {
+                self.0 == *other
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let id = String::deserialize(deserializer)?;
+                Self::new(id).map_err(serde::de::Error::custom)
+            }
+        }
+    };
+}
+
+id_type!(
+    /// The `_id` of a post, e.g. `7ZqGiPHTpiDMwqMN2`.
+    PostId,
+    "post"
+);
+id_type!(
+    /// The `_id` of a comment.
+    CommentId,
+    "comment"
+);
+id_type!(
+    /// The `_id` of a user.
+    UserId,
+    "user"
+);
+id_type!(
+    /// The `_id` of a tag.
+    TagId,
+    "tag"
+);
+id_type!(
+    /// The `_id` of a sequence.
+    SequenceId,
+    "sequence"
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_ids() {
+        let id: PostId = "7ZqGiPHTpiDMwqMN2".parse().unwrap();
+        assert_eq!(id, "7ZqGiPHTpiDMwqMN2");
+        assert_eq!(serde_json::to_string(&id).unwrap(), "\"7ZqGiPHTpiDMwqMN2\"");
+        assert!("5f5c37ee1b5cdee568cfb2ac".parse::<TagId>().is_ok());
+
+        // too short, and `0`, `O`, `l` and `-` are not part of the alphabet
+        for invalid in [
+            "123456",
+            "0ZqGiPHTpiDMwqMN2",
+            "OZqGiPHTpiDMwqMN2",
+            "lZqGiPHTpiDMwqMN2",
+            "twelve-virtues-of",
+        ] {
+            assert!(matches!(
+                invalid.parse::<PostId>(),
+                Err(Error::InvalidId { kind: "post", .. })
+            ));
+        }
+        assert!(serde_json::from_str::<CommentId>("\"123456\"").is_err());
+    }
+}
@@ -0,0 +1,355 @@
+use chrono::{DateTime, Utc};
+use futures::{stream, Stream, TryStreamExt};
+
+use crate::{
+    post_query, posts_list_query, Error, LessWrongApiClient, Post, PostsListQuery,
+    PostsResponseData, TagId, UserId, JSON,
+};
+
+/// The views of the `posts` resolver, which decide the order and the base filter of the results.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum PostsView {
+    /// The frontpage ranking of recent, highly voted posts.
+    #[default]
+    Magic,
+    New,
+    Old,
+    Top,
+    RecentComments,
+    Curated,
+    Frontpage,
+    Daily,
+    /// Posts of the author set with [`PostsQuery::author`].
+    UserPosts,
+    /// Posts of the tag set with [`PostsQuery::tag`], by relevance.
+    TagRelevance,
+    Questions,
+    Events,
+    /// Any other view the server knows.
+    Custom(String),
+}
+
+impl PostsView {
+    pub fn as_str(&self) -> &str {
+        match self {
+            PostsView::Magic => "magic",
+            PostsView::New => "new",
+            PostsView::Old => "old",
+            PostsView::Top => "top",
+            PostsView::RecentComments => "recentComments",
+            PostsView::Curated => "curated",
+            PostsView::Frontpage => "frontpage",
+            PostsView::Daily => "daily",
+            PostsView::UserPosts => "userPosts",
+            PostsView::TagRelevance => "tagRelevance",
+            PostsView::Questions => "questions",
+            PostsView::Events => "events",
+            PostsView::Custom(view) => view,
+        }
+    }
+}
+
+/// A listing of posts for [`LessWrongApiClient::list_posts`].
+///
+/// ```
+/// # use lesswrong_api::{PostsQuery, PostsView};
+/// let query = PostsQuery::new(PostsView::Top)
+///     .karma_threshold(100)
+///     .question(false)
+///     .page_size(20);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct PostsQuery {
+    view: PostsView,
+    author: Option<UserId>,
+    tag: Option<TagId>,
+    after: Option<DateTime<Utc>>,
+    before: Option<DateTime<Utc>>,
+    karma_threshold: Option<i64>,
+    question: Option<bool>,
+    event: Option<bool>,
+    link: Option<bool>,
+    offset: usize,
+    page_size: usize,
+}
+
+impl Default for PostsQuery {
+    fn default() -> Self {
+        Self::new(PostsView::default())
+    }
+}
+
+impl PostsQuery {
+    pub fn new(view: PostsView) -> Self {
+        Self {
+            view,
+            author: None,
+            tag: None,
+            after: None,
+            before: None,
+            karma_threshold: None,
+            question: None,
+            event: None,
+            link: None,
+            offset: 0,
+            page_size: 50,
+        }
+    }
+
+    pub fn author(mut self, author: UserId) -> Self {
+        self.author = Some(author);
+        self
+    }
+
+    pub fn tag(mut self, tag: TagId) -> Self {
+        self.tag = Some(tag);
+        self
+    }
+
+    /// Only posts posted at or after `after`.
+    pub fn after(mut self, after: DateTime<Utc>) -> Self {
+        self.after = Some(after);
+        self
+    }
+
+    /// Only posts posted before `before`.
+    pub fn before(mut self, before: DateTime<Utc>) -> Self {
+        self.before = Some(before);
+        self
+    }
+
+    /// Only posts with at least this karma (`baseScore`).
+    pub fn karma_threshold(mut self, karma_threshold: i64) -> Self {
+        self.karma_threshold = Some(karma_threshold);
+        self
+    }
+
+    /// Only questions (`true`) or only posts that are no questions (`false`).
+    ///
+    /// The server has no term for this, the fetched pages are filtered instead, so a view where
+    /// few posts match may take many requests per post. [`PostsView::Questions`] lists only
+    /// questions on the server.
+    pub fn question(mut self, question: bool) -> Self {
+        self.question = Some(question);
+        self
+    }
+
+    /// Only events (`true`) or only posts that are no events (`false`).
+    ///
+    /// Filtered on the fetched pages like [`Self::question`], [`PostsView::Events`] lists only
+    /// events on the server.
+    pub fn event(mut self, event: bool) -> Self {
+        self.event = Some(event);
+        self
+    }
+
+    /// Only link posts (`true`) or only posts that link nowhere (`false`).
+    ///
+    /// Filtered on the fetched pages like [`Self::question`], there is no view of link posts.
+    pub fn link(mut self, link: bool) -> Self {
+        self.link = Some(link);
+        self
+    }
+
+    /// Number of posts to skip. Defaults to 0.
+    pub fn offset(mut self, offset: usize) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Number of posts requested at once. Defaults to 50.
+    pub fn page_size(mut self, page_size: usize) -> Self {
+        self.page_size = page_size.max(1);
+        self
+    }
+
+    fn terms(&self, offset: usize) -> JSON {
+        let mut terms = serde_json::Map::new();
+        terms.insert("view".to_string(), self.view.as_str().into());
+        terms.insert("offset".to_string(), offset.into());
+        terms.insert("limit".to_string(), self.page_size.into());
+        if let Some(author) = &self.author {
+            terms.insert("userId".to_string(), author.as_str().into());
+        }
+        if let Some(tag) = &self.tag {
+            terms.insert("tagId".to_string(), tag.as_str().into());
+            // views other than `tagRelevance` ignore `tagId` and filter by the filter settings
+            terms.insert(
+                "filterSettings".to_string(),
+                serde_json::json!({ "tags": [{ "tagId": tag.as_str(), "filterMode": "Required" }] }),
+            );
+        }
+        if let Some(after) = self.after {
+            terms.insert("after".to_string(), after.to_rfc3339().into());
+        }
+        if let Some(before) = self.before {
+            terms.insert("before".to_string(), before.to_rfc3339().into());
+        }
+        if let Some(karma_threshold) = self.karma_threshold {
+            terms.insert("karmaThreshold".to_string(), karma_threshold.into());
+        }
+        JSON::Object(terms)
+    }
+
+    /// The question, event and link flags have no terms and are checked on the results instead.
+    fn matches(&self, post: &post_query::PostFields) -> bool {
+        let is_link = post.url.as_ref().is_some_and(|url| !url.is_empty());
+        self.question
+            .is_none_or(|question| post.question.unwrap_or(false) == question)
+            && self
+                .event
+                .is_none_or(|event| post.is_event.unwrap_or(false) == event)
+            && self.link.is_none_or(|link| is_link == link)
+    }
+
+    /// Filters, orders and skips decoded posts like the server would, for
+    /// [`crate::FakeLessWrong`]. Views without an obvious order list the newest posts first.
+    pub(crate) fn apply<'a>(&self, posts: impl IntoIterator<Item = &'a Post>) -> Vec<Post> {
+        let mut posts: Vec<Post> = posts
+            .into_iter()
+            .filter(|post| {
+                self.author
+                    .as_ref()
+                    .is_none_or(|author| post.user_id.as_ref() == Some(author))
+                    && self
+                        .tag
+                        .as_ref()
+                        .is_none_or(|tag| post.tags.iter().any(|t| &t.tag.id == tag))
+                    && self.after.is_none_or(|after| post.date >= after)
+                    && self.before.is_none_or(|before| post.date < before)
+                    && self
+                        .karma_threshold
+                        .is_none_or(|karma| post.base_score >= karma as f64)
+                    && self
+                        .question
+                        .is_none_or(|question| post.question.unwrap_or(false) == question)
+                    && self
+                        .event
+                        .is_none_or(|event| post.is_event.unwrap_or(false) == event)
+                    && self.link.is_none_or(|link| post.url.is_some() == link)
+            })
+            .cloned()
+            .collect();
+        match self.view {
+            PostsView::Old => posts.sort_by_key(|post| post.date),
+            PostsView::Top => posts.sort_by(|a, b| b.base_score.total_cmp(&a.base_score)),
+            _ => posts.sort_by_key(|post| std::cmp::Reverse(post.date)),
+        }
+        posts.into_iter().skip(self.offset).collect()
+    }
+}
+
+impl LessWrongApiClient {
+    /// Lists the posts of a view, requesting `page_size` posts at a time while the stream is
+    /// polled. The stream ends after the first page with fewer posts or after a failed request.
+    pub fn list_posts(
+        &self,
+        query: PostsQuery,
+    ) -> impl Stream<Item = Result<Post, Error>> + Send + 'static {
+        let client = self.clone();
+        let offset = Some(query.offset);
+        stream::try_unfold(
+            (client, query, offset),
+            |(client, query, offset)| async move {
+                let Some(offset) = offset else {
+                    return Ok::<_, Error>(None);
+                };
+                let page = client.fetch_posts_page(&query, offset).await?;
+                let next_offset = (page.len() >= query.page_size).then_some(offset + page.len());
+                let posts: Vec<_> = page
+                    .into_iter()
+                    .filter(|post| query.matches(post))
+                    .map(|post| client.decode_post(post))
+                    .collect();
+                Ok(Some((stream::iter(posts), (client, query, next_offset))))
+            },
+        )
+        .try_flatten()
+    }
+
+    async fn fetch_posts_page(
+        &self,
+        query: &PostsQuery,
+        offset: usize,
+    ) -> Result<Vec<post_query::PostFields>, Error> {
+        let variables = posts_list_query::Variables {
+            terms: Some(query.terms(offset)),
+            extended: self.extended_fields(),
+        };
+        Ok(self
+            .post_graphql_as::<PostsListQuery, PostsResponseData>(variables)
+            .await?
+            .posts
+            .and_then(|posts| posts.results)
+            .ok_or(Error::malformatted("posts.results"))?
+            .into_iter()
+            .flatten()
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{FakeResponse, FakeServer};
+    use futures::StreamExt;
+
+    #[tokio::test]
+    async fn test_list_posts() {
+        let post = |id: &str, question: bool| {
+            serde_json::json!({
+                "_id": id,
+                "title": id,
+                "author": "Eliezer Yudkowsky",
+                "postedAt": "2006-01-01T08:00:05.370Z",
+                "slug": "slug",
+                "pageUrl": format!("https://www.lesswrong.com/posts/{}/slug", id),
+                "baseScore": 100.0,
+                "wordCount": 10,
+                "readTimeMinutes": 1,
+                "htmlBody": "<p>hi</p>",
+                "contents": { "markdown": "hi" },
+                "question": question
+            })
+        };
+        let server = FakeServer::start().await;
+        server.respond_once(
+            "PostsListQuery",
+            FakeResponse::data(serde_json::json!({ "posts": { "results": [
+                post("aaaaaaaaaaaaaaaaa", false),
+                post("bbbbbbbbbbbbbbbbb", true),
+            ] } })),
+        );
+        server.respond_once(
+            "PostsListQuery",
+            FakeResponse::data(serde_json::json!({ "posts": { "results": [
+                post("ccccccccccccccccc", false),
+            ] } })),
+        );
+
+        let query = PostsQuery::new(PostsView::Top)
+            .author("nmk3nLpQE89dMRzzN".parse().unwrap())
+            .karma_threshold(50)
+            .question(false)
+            .page_size(2);
+        let posts: Vec<_> = server.client().list_posts(query).collect().await;
+        let ids: Vec<_> = posts
+            .into_iter()
+            .map(|post| post.unwrap().id.into_inner())
+            .collect();
+        assert_eq!(ids, ["aaaaaaaaaaaaaaaaa", "ccccccccccccccccc"]);
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(
+            requests[1]["variables"]["terms"],
+            serde_json::json!({
+                "view": "top",
+                "offset": 2,
+                "limit": 2,
+                "userId": "nmk3nLpQE89dMRzzN",
+                "karmaThreshold": 50
+            })
+        );
+    }
+}
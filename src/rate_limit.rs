@@ -0,0 +1,155 @@
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::{Semaphore, SemaphorePermit};
+use tokio::time::Instant;
+
+use crate::Error;
+
+/// Client-side throttling applied to every request the client sends, retries included.
+/// The limit is shared by all clones of a client.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateLimit {
+    /// Sustained request rate of the token bucket, must be positive.
+    pub requests_per_second: f64,
+    /// Number of requests that can be sent back-to-back before the rate kicks in.
+    pub burst: u32,
+    /// Maximum number of requests waiting for a response at the same time, must be positive.
+    pub max_in_flight: Option<usize>,
+}
+
+impl RateLimit {
+    /// A rate that is not positive would make the bucket never refill.
+    pub(crate) fn validate(&self) -> Result<(), Error> {
+        if self.requests_per_second.is_nan() || self.requests_per_second <= 0.0 {
+            return Err(Error::InvalidRateLimit(
+                "requests_per_second must be positive",
+            ));
+        }
+        if self.max_in_flight == Some(0) {
+            return Err(Error::InvalidRateLimit("max_in_flight must be positive"));
+        }
+        Ok(())
+    }
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self {
+            requests_per_second: 2.0,
+            burst: 5,
+            max_in_flight: Some(4),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    bucket: Mutex<Bucket>,
+    in_flight: Semaphore,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(limit: &RateLimit) -> Self {
+        let burst = f64::from(limit.burst.max(1));
+        Self {
+            rate: limit.requests_per_second,
+            burst,
+            bucket: Mutex::new(Bucket {
+                tokens: burst,
+                refilled_at: Instant::now(),
+            }),
+            in_flight: Semaphore::new(limit.max_in_flight.unwrap_or(Semaphore::MAX_PERMITS)),
+        }
+    }
+
+    /// Waits until a request may be sent. The request counts as in flight until the permit is dropped.
+    pub(crate) async fn acquire(&self) -> SemaphorePermit<'_> {
+        let permit = self
+            .in_flight
+            .acquire()
+            .await
+            .expect("rate limiter semaphore is never closed");
+
+        loop {
+            let wait = {
+                let mut bucket = self.bucket.lock().unwrap();
+                let now = Instant::now();
+                let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+                bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.burst);
+                bucket.refilled_at = now;
+
+                if bucket.tokens >= 1.0 {
+                    bucket.tokens -= 1.0;
+                    return permit;
+                }
+                // a tiny rate makes the wait too long for a `Duration`, it is as good as forever
+                Duration::try_from_secs_f64((1.0 - bucket.tokens) / self.rate)
+                    .unwrap_or(Duration::MAX)
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invalid_rate_limit() {
+        let build = |limit: RateLimit| {
+            crate::LessWrongApiClient::builder()
+                .rate_limit(limit)
+                .build()
+        };
+        for requests_per_second in [0.0, -1.0, f64::NAN] {
+            let limit = RateLimit {
+                requests_per_second,
+                ..Default::default()
+            };
+            assert!(matches!(build(limit), Err(Error::InvalidRateLimit(_))));
+        }
+        let limit = RateLimit {
+            max_in_flight: Some(0),
+            ..Default::default()
+        };
+        assert!(matches!(build(limit), Err(Error::InvalidRateLimit(_))));
+        assert!(build(RateLimit::default()).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_subnormal_rate() {
+        let limiter = RateLimiter::new(&RateLimit {
+            requests_per_second: 1e-320,
+            burst: 1,
+            max_in_flight: None,
+        });
+        drop(limiter.acquire().await);
+        let second = tokio::time::timeout(Duration::from_millis(20), limiter.acquire()).await;
+        assert!(second.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_token_bucket() {
+        let limiter = RateLimiter::new(&RateLimit {
+            requests_per_second: 50.0,
+            burst: 2,
+            max_in_flight: None,
+        });
+
+        let start = tokio::time::Instant::now();
+        for _ in 0..4 {
+            drop(limiter.acquire().await);
+        }
+        // the burst is free, the two remaining requests are spaced by 20ms each
+        assert!(start.elapsed() >= Duration::from_millis(40));
+    }
+}
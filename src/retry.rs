@@ -0,0 +1,156 @@
+use rand::Rng;
+use reqwest::{header::RETRY_AFTER, StatusCode};
+use std::time::Duration;
+
+use crate::Error;
+
+/// How failed requests are retried. Only errors classified by [`crate::Error::is_retryable`] are
+/// retried, with exponential backoff between the attempts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one. `1` disables retries.
+    pub max_attempts: u32,
+    /// Backoff before the first retry, doubled (see `multiplier`) for every following retry.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    pub multiplier: f64,
+    /// Randomizes each backoff between half and the full computed value so that concurrent
+    /// requests failing at the same time don't retry in lockstep.
+    pub jitter: bool,
+    /// Longest `Retry-After` the client is willing to wait for on a 429 or 503.
+    /// If the server asks for more, the error is returned instead.
+    pub max_retry_after: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            multiplier: 2.0,
+            jitter: true,
+            max_retry_after: Duration::from_secs(60),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    /// A negative or non-finite multiplier would make the backoff negative or NaN, which panics
+    /// computing the delay.
+    pub(crate) fn validate(&self) -> Result<(), Error> {
+        if !self.multiplier.is_finite() || self.multiplier < 0.0 {
+            return Err(Error::InvalidRetryPolicy(
+                "multiplier must be finite and not negative",
+            ));
+        }
+        Ok(())
+    }
+
+    /// The delay before the next attempt, `attempt` being the number of attempts made so far.
+    /// Returns `None` if no further attempt should be made.
+    pub(crate) fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Option<Duration> {
+        if attempt >= self.max_attempts {
+            return None;
+        }
+        if let Some(retry_after) = retry_after {
+            return (retry_after <= self.max_retry_after).then_some(retry_after);
+        }
+
+        let backoff = self.initial_backoff.as_secs_f64()
+            * self.multiplier.powi(attempt.saturating_sub(1) as i32);
+        let backoff = backoff.min(self.max_backoff.as_secs_f64());
+        let backoff = if self.jitter {
+            rand::thread_rng().gen_range(backoff / 2.0..=backoff)
+        } else {
+            backoff
+        };
+        // a `max_backoff` close to `Duration::MAX` is rounded up past it as `f64`
+        Some(Duration::try_from_secs_f64(backoff).unwrap_or(self.max_backoff))
+    }
+}
+
+/// Parses the `Retry-After` header of 429 and 503 responses, given either in seconds or as an HTTP date.
+pub(crate) fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+    if !matches!(
+        response.status(),
+        StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE
+    ) {
+        return None;
+    }
+
+    let value = response.headers().get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    // a date in the past means we can retry right away
+    Some(
+        (date.with_timezone(&chrono::Utc) - chrono::Utc::now())
+            .to_std()
+            .unwrap_or_default(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exponential_backoff() {
+        let policy = RetryPolicy {
+            max_attempts: 5,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(3),
+            jitter: false,
+            ..Default::default()
+        };
+        assert_eq!(policy.delay(1, None), Some(Duration::from_secs(1)));
+        assert_eq!(policy.delay(2, None), Some(Duration::from_secs(2)));
+        assert_eq!(policy.delay(3, None), Some(Duration::from_secs(3)));
+        assert_eq!(policy.delay(5, None), None);
+        assert_eq!(
+            policy.delay(1, Some(Duration::from_secs(10))),
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(policy.delay(1, Some(Duration::from_secs(61))), None);
+        assert_eq!(RetryPolicy::none().delay(1, None), None);
+    }
+
+    #[test]
+    fn test_invalid_retry_policy() {
+        let build = |policy: RetryPolicy| {
+            crate::LessWrongApiClient::builder()
+                .retry_policy(policy)
+                .build()
+        };
+        for multiplier in [-2.0, f64::NAN, f64::INFINITY] {
+            let policy = RetryPolicy {
+                multiplier,
+                ..Default::default()
+            };
+            assert!(matches!(build(policy), Err(Error::InvalidRetryPolicy(_))));
+        }
+        let policy = RetryPolicy {
+            multiplier: 0.0,
+            ..Default::default()
+        };
+        assert!(build(policy).is_ok());
+
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            max_backoff: Duration::MAX,
+            multiplier: 1e10,
+            jitter: false,
+            ..Default::default()
+        };
+        assert_eq!(policy.delay(9, None), Some(Duration::MAX));
+    }
+}
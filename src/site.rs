@@ -0,0 +1,88 @@
+use reqwest::Url;
+
+use crate::Error;
+
+/// A ForumMagnum instance the client talks to.
+///
+/// LessWrong, the Alignment Forum and the EA Forum all run ForumMagnum and share the same GraphQL
+/// schema, so every query in this crate works against any of them.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Site {
+    #[default]
+    LessWrong,
+    AlignmentForum,
+    EaForum,
+    /// Any other ForumMagnum-compatible server, given by its base URL, e.g. `http://127.0.0.1:3000`.
+    /// The GraphQL endpoint is expected at `<base>/graphql`.
+    Custom(String),
+}
+
+impl Site {
+    pub fn base_url(&self) -> &str {
+        match self {
+            Site::LessWrong => "https://www.lesswrong.com",
+            Site::AlignmentForum => "https://www.alignmentforum.org",
+            Site::EaForum => "https://forum.effectivealtruism.org",
+            Site::Custom(url) => url.trim_end_matches('/'),
+        }
+    }
+
+    pub fn graphql_endpoint(&self) -> Result<Url, Error> {
+        let url = format!("{}/graphql", self.base_url());
+        Url::parse(&url).map_err(|_| Error::InvalidUrl(url))
+    }
+
+    /// Rewrites a `pageUrl` returned by the server so that it points at this site.
+    /// The server may answer with the URL of the forum a post was originally published on,
+    /// we only keep its path, query and fragment.
+    pub(crate) fn page_url(&self, server_url: &str) -> String {
+        match Url::parse(server_url) {
+            Ok(url) => {
+                let mut page_url = format!("{}{}", self.base_url(), url.path());
+                if let Some(query) = url.query() {
+                    page_url.push('?');
+                    page_url.push_str(query);
+                }
+                if let Some(fragment) = url.fragment() {
+                    page_url.push('#');
+                    page_url.push_str(fragment);
+                }
+                page_url
+            }
+            // relative URL
+            Err(_) if server_url.starts_with('/') => format!("{}{}", self.base_url(), server_url),
+            Err(_) => server_url.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_url_rewrites_origin() {
+        let url = "https://www.lesswrong.com/posts/7ZqGiPHTpiDMwqMN2/twelve-virtues-of-rationality";
+        assert_eq!(Site::LessWrong.page_url(url), url);
+        assert_eq!(
+            Site::AlignmentForum.page_url(url),
+            "https://www.alignmentforum.org/posts/7ZqGiPHTpiDMwqMN2/twelve-virtues-of-rationality"
+        );
+        assert_eq!(
+            Site::Custom("http://127.0.0.1:3000/".to_string())
+                .page_url("/posts/abc/slug?commentId=xyz"),
+            "http://127.0.0.1:3000/posts/abc/slug?commentId=xyz"
+        );
+    }
+
+    #[test]
+    fn test_graphql_endpoint() {
+        assert_eq!(
+            Site::EaForum.graphql_endpoint().unwrap().as_str(),
+            "https://forum.effectivealtruism.org/graphql"
+        );
+        assert!(Site::Custom("not a url".to_string())
+            .graphql_endpoint()
+            .is_err());
+    }
+}
@@ -0,0 +1,124 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    post_query, post_tags_query, Error, LessWrongApiClient, PostId, PostTagsQuery, TagId, JSON,
+};
+
+/// A tag, as summarized on the posts it is applied to.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Tag {
+    pub id: TagId,
+    pub name: String,
+    pub slug: String,
+    /// Core tags are the broad ones offered as frontpage filters, e.g. "AI" or "Rationality".
+    pub core: bool,
+}
+
+/// A tag applied to a post.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PostTag {
+    pub tag: Tag,
+    /// The score of the votes on applying the tag to the post, from the post's `tagRelevance`.
+    pub relevance: f64,
+}
+
+/// The response of `PostTagsQuery`, decoded into `post_query::TagFields` to share
+/// [`decode_tags`] with the posts.
+#[derive(Serialize, Deserialize)]
+struct PostTagsResponseData {
+    post: Option<SinglePostTagsOutput>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SinglePostTagsOutput {
+    result: Option<PostTagsResult>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PostTagsResult {
+    tags: Option<Vec<Option<post_query::TagFields>>>,
+    tag_relevance: Option<JSON>,
+}
+
+/// Pairs the tags with their scores in `tag_relevance`, most relevant first.
+/// Tags with a malformed ID are skipped.
+pub(crate) fn decode_tags(
+    tags: Option<Vec<Option<post_query::TagFields>>>,
+    tag_relevance: Option<JSON>,
+) -> Vec<PostTag> {
+    let relevance = |id: &TagId| {
+        tag_relevance
+            .as_ref()
+            .and_then(|scores| scores.get(id.as_str()))
+            .and_then(JSON::as_f64)
+            .unwrap_or_default()
+    };
+    let mut tags: Vec<PostTag> = tags
+        .unwrap_or_default()
+        .into_iter()
+        .flatten()
+        .filter_map(|tag| {
+            let id = TagId::new(tag.id?).ok()?;
+            Some(PostTag {
+                relevance: relevance(&id),
+                tag: Tag {
+                    id,
+                    name: tag.name.unwrap_or_default(),
+                    slug: tag.slug.unwrap_or_default(),
+                    core: tag.core.unwrap_or(false),
+                },
+            })
+        })
+        .collect();
+    tags.sort_by(|a, b| b.relevance.total_cmp(&a.relevance));
+    tags
+}
+
+impl LessWrongApiClient {
+    /// Fetches only the tags of a post, most relevant first, without its body.
+    pub async fn get_post_tags(&self, post_id: &PostId) -> Result<Vec<PostTag>, Error> {
+        let variables = post_tags_query::Variables {
+            id: post_id.to_string(),
+        };
+        let post = self
+            .post_graphql_as::<PostTagsQuery, PostTagsResponseData>(variables)
+            .await?
+            .post
+            .ok_or(Error::NotFound)?
+            .result
+            .ok_or(Error::malformatted("post"))?;
+        Ok(decode_tags(post.tags, post.tag_relevance))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{FakeResponse, FakeServer};
+
+    #[tokio::test]
+    async fn test_get_post_tags() {
+        let server = FakeServer::start().await;
+        server.respond(
+            "PostTagsQuery",
+            FakeResponse::data(serde_json::json!({ "post": { "result": {
+                "tags": [
+                    { "_id": "Ng8Gice9KNkncxqcj", "name": "Rationality", "slug": "rationality", "core": true },
+                    { "_id": "bad", "name": "Broken", "slug": "broken", "core": false },
+                    { "_id": "5f5c37ee1b5cdee568cfb2ac", "name": "Virtues", "slug": "virtues", "core": false },
+                ],
+                "tagRelevance": { "Ng8Gice9KNkncxqcj": 12, "5f5c37ee1b5cdee568cfb2ac": 31 }
+            } } })),
+        );
+
+        let tags = server
+            .client()
+            .get_post_tags(&"7ZqGiPHTpiDMwqMN2".parse().unwrap())
+            .await
+            .unwrap();
+        let names: Vec<_> = tags.iter().map(|tag| tag.tag.name.as_str()).collect();
+        assert_eq!(names, ["Virtues", "Rationality"]);
+        assert_eq!(tags[0].relevance, 31.0);
+        assert!(tags[1].tag.core);
+    }
+}
@@ -0,0 +1,327 @@
+//! A local GraphQL server for offline tests, enabled with the `testing` feature.
+//!
+//! The server answers each query by its operation name (`PostQuery`, `CommentsQuery`, ...) with a
+//! canned response, which can also be a failure such as a 500, a 429 or a malformed body.
+//!
+//! ```
+//! # use lesswrong_api::testing::{FakeResponse, FakeServer};
+//! # #[tokio::main]
+//! # async fn main() {
+//! let server = FakeServer::start().await;
+//! server.respond("PostQuery", FakeResponse::graphql_error("app.document_not_found"));
+//!
+//! let client = server.client();
+//! let post_id = "7ZqGiPHTpiDMwqMN2".parse().unwrap();
+//! assert!(client.get_post(&post_id).await.is_err());
+//! # }
+//! ```
+
+use reqwest::StatusCode;
+use serde_json::Value as JSON;
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+use crate::{LessWrongApiClient, LessWrongApiClientBuilder, RetryPolicy, Site};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FakeResponse {
+    /// A 200 with the given JSON as the whole body, e.g. `{"data": ...}`.
+    Json(JSON),
+    /// The given status with a text body.
+    Status(StatusCode, String),
+    /// A 429 with a `Retry-After` header.
+    RateLimited { retry_after: Duration },
+    /// A 200 with a body that is not valid JSON.
+    Malformed(String),
+    /// Sends the response headers, then nothing for the given duration before closing the connection.
+    Stall(Duration),
+}
+
+impl FakeResponse {
+    pub fn data(data: JSON) -> Self {
+        FakeResponse::Json(serde_json::json!({ "data": data }))
+    }
+
+    pub fn graphql_error(message: &str) -> Self {
+        FakeResponse::Json(serde_json::json!({
+            "errors": [{ "message": message }],
+            "data": null
+        }))
+    }
+
+    /// Reads a JSON response body from a file.
+    pub fn from_fixture(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let json = std::fs::read_to_string(path)?;
+        Ok(FakeResponse::Json(serde_json::from_str(&json)?))
+    }
+}
+
+/// The responses under `tests/fixtures`, shared by the unit tests.
+#[cfg(test)]
+impl FakeResponse {
+    pub(crate) fn fixture(name: &str) -> Self {
+        FakeResponse::Json(Self::fixture_json(name))
+    }
+
+    pub(crate) fn fixture_json(name: &str) -> JSON {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name);
+        serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    /// The post of `post_query.json`, to build other responses containing it.
+    pub(crate) fn post_fixture_result() -> JSON {
+        Self::fixture_json("post_query.json")["data"]["post"]["result"].clone()
+    }
+}
+
+type Handler = Arc<dyn Fn(&JSON) -> FakeResponse + Send + Sync>;
+
+#[derive(Default)]
+struct State {
+    responses: HashMap<String, FakeResponse>,
+    handlers: HashMap<String, Handler>,
+    queued: HashMap<String, VecDeque<FakeResponse>>,
+    requests: Vec<JSON>,
+    headers: Vec<HashMap<String, String>>,
+}
+
+impl std::fmt::Debug for State {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("State")
+            .field("responses", &self.responses)
+            .field("handlers", &self.handlers.keys().collect::<Vec<_>>())
+            .field("queued", &self.queued)
+            .field("requests", &self.requests)
+            .field("headers", &self.headers)
+            .finish()
+    }
+}
+
+impl State {
+    fn response_for(&mut self, operation: &str, request: &JSON) -> FakeResponse {
+        if let Some(response) = self.queued.get_mut(operation).and_then(VecDeque::pop_front) {
+            return response;
+        }
+        if let Some(handler) = self.handlers.get(operation) {
+            return handler(request);
+        }
+        self.responses.get(operation).cloned().unwrap_or_else(|| {
+            FakeResponse::Status(
+                StatusCode::BAD_REQUEST,
+                format!("no response configured for operation '{}'", operation),
+            )
+        })
+    }
+}
+
+/// A GraphQL server listening on a random local port until dropped.
+#[derive(Debug)]
+pub struct FakeServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    handle: JoinHandle<()>,
+}
+
+impl FakeServer {
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("failed to bind fake server");
+        let addr = listener.local_addr().expect("fake server has no address");
+        let state = Arc::new(Mutex::new(State::default()));
+
+        let server_state = state.clone();
+        let handle = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(handle_connection(stream, server_state.clone()));
+            }
+        });
+
+        Self {
+            addr,
+            state,
+            handle,
+        }
+    }
+
+    pub fn site(&self) -> Site {
+        Site::Custom(format!("http://{}", self.addr))
+    }
+
+    /// A client builder pointing at this server. Retries are disabled so that injected failures
+    /// surface directly, set a `RetryPolicy` to test them.
+    pub fn client_builder(&self) -> LessWrongApiClientBuilder {
+        LessWrongApiClient::builder()
+            .site(self.site())
+            .retry_policy(RetryPolicy::none())
+    }
+
+    pub fn client(&self) -> LessWrongApiClient {
+        self.client_builder()
+            .build()
+            .expect("fake server client configuration should be valid")
+    }
+
+    /// Answers every query with the given operation name with `response`.
+    pub fn respond(&self, operation: &str, response: FakeResponse) {
+        self.state
+            .lock()
+            .unwrap()
+            .responses
+            .insert(operation.to_string(), response);
+    }
+
+    /// Answers every query with the given operation name with the response `handler` builds from
+    /// the request body, e.g. depending on its variables. Takes precedence over [`Self::respond`].
+    pub fn respond_with(
+        &self,
+        operation: &str,
+        handler: impl Fn(&JSON) -> FakeResponse + Send + Sync + 'static,
+    ) {
+        self.state
+            .lock()
+            .unwrap()
+            .handlers
+            .insert(operation.to_string(), Arc::new(handler));
+    }
+
+    /// Answers the next query with the given operation name with `response`, taking precedence
+    /// over [`Self::respond`] and [`Self::respond_with`]. Queued responses are used in order.
+    pub fn respond_once(&self, operation: &str, response: FakeResponse) {
+        self.state
+            .lock()
+            .unwrap()
+            .queued
+            .entry(operation.to_string())
+            .or_default()
+            .push_back(response);
+    }
+
+    /// The bodies of all GraphQL requests received so far.
+    pub fn requests(&self) -> Vec<JSON> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    /// The headers of all GraphQL requests received so far, in the order of [`Self::requests`].
+    /// Header names are lowercase.
+    pub fn request_headers(&self) -> Vec<HashMap<String, String>> {
+        self.state.lock().unwrap().headers.clone()
+    }
+}
+
+impl Drop for FakeServer {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+async fn handle_connection(mut stream: TcpStream, state: Arc<Mutex<State>>) {
+    let Some((headers, body)) = read_request(&mut stream).await else {
+        return;
+    };
+    let request: JSON = serde_json::from_slice(&body).unwrap_or_default();
+    let operation = request["operationName"]
+        .as_str()
+        .unwrap_or_default()
+        .to_string();
+
+    let response = {
+        let mut state = state.lock().unwrap();
+        let response = state.response_for(&operation, &request);
+        state.requests.push(request);
+        state.headers.push(headers);
+        response
+    };
+
+    let _ = match response {
+        FakeResponse::Json(json) => {
+            write_response(&mut stream, StatusCode::OK, &[], &json.to_string()).await
+        }
+        FakeResponse::Status(status, text) => write_response(&mut stream, status, &[], &text).await,
+        FakeResponse::RateLimited { retry_after } => {
+            let retry_after = retry_after.as_secs().to_string();
+            write_response(
+                &mut stream,
+                StatusCode::TOO_MANY_REQUESTS,
+                &[("Retry-After", &retry_after)],
+                "rate limited",
+            )
+            .await
+        }
+        FakeResponse::Malformed(body) => {
+            write_response(&mut stream, StatusCode::OK, &[], &body).await
+        }
+        FakeResponse::Stall(duration) => {
+            let head = "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: 1000\r\n\r\n{";
+            let _ = stream.write_all(head.as_bytes()).await;
+            tokio::time::sleep(duration).await;
+            Ok(())
+        }
+    };
+}
+
+async fn read_request(stream: &mut TcpStream) -> Option<(HashMap<String, String>, Vec<u8>)> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let header_end = loop {
+        let n = stream.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+    };
+
+    // the first line is the request line
+    let headers: HashMap<String, String> = String::from_utf8_lossy(&buf[..header_end])
+        .lines()
+        .skip(1)
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect();
+    let content_length = headers
+        .get("content-length")
+        .and_then(|value| value.parse::<usize>().ok())
+        .unwrap_or(0);
+
+    let mut body = buf.split_off(header_end);
+    while body.len() < content_length {
+        let n = stream.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        body.extend_from_slice(&chunk[..n]);
+    }
+    Some((headers, body))
+}
+
+async fn write_response(
+    stream: &mut TcpStream,
+    status: StatusCode,
+    headers: &[(&str, &str)],
+    body: &str,
+) -> std::io::Result<()> {
+    let mut response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+        status.as_u16(),
+        status.canonical_reason().unwrap_or_default(),
+        body.len()
+    );
+    for (name, value) in headers {
+        response.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    response.push_str("\r\n");
+    response.push_str(body);
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}